@@ -0,0 +1,199 @@
+//! Unconnected ping of the RakNet protocol, which is used to query the status
+//! of bedrock edition servers.
+
+use crate::connect_udp;
+use miette::{bail, miette, IntoDiagnostic, WrapErr};
+use serde_json::{json, Value};
+use std::{
+    net::IpAddr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::info;
+
+/// the port bedrock servers listen on by default
+pub const DEFAULT_PORT: u16 = 19132;
+
+/// magic bytes contained in offline RakNet packets
+const MAGIC: [u8; 16] = [
+    0x00, 0xff, 0xff, 0x00, 0xfe, 0xfe, 0xfe, 0xfe, 0xfd, 0xfd, 0xfd, 0xfd, 0x12, 0x34, 0x56, 0x78,
+];
+
+const CLIENT_GUID: u64 = 0x6d63_7374_6174_0001;
+
+/// response to a bedrock unconnected ping
+pub struct BedrockStatus {
+    /// `MCPE` for bedrock edition, `MCEE` for education edition
+    pub edition: String,
+    pub motd: String,
+    pub protocol: u32,
+    pub version: String,
+    pub online: u32,
+    pub max: u32,
+    /// the second line of the MOTD, usually the name of the world
+    pub level_name: Option<String>,
+    pub game_mode: Option<String>,
+}
+
+impl BedrockStatus {
+    /// converts this status to the format of a java edition status response,
+    /// so it can be handled the same way
+    pub fn to_json(&self) -> Value {
+        let description = match &self.level_name {
+            Some(level_name) => format!("{}\n{}", self.motd, level_name),
+            None => self.motd.clone(),
+        };
+
+        json!({
+            "version": {
+                "name": self.version,
+                "protocol": self.protocol,
+            },
+            "players": {
+                "online": self.online,
+                "max": self.max,
+            },
+            "description": {
+                "text": description,
+                "extra": [],
+            },
+        })
+    }
+}
+
+/// sends an unconnected ping to the server and parses the response
+pub async fn ping(addr: &str, port: u16, bind: Option<IpAddr>) -> miette::Result<BedrockStatus> {
+    let socket = connect_udp(addr, port, bind).await?;
+
+    info!("Sending unconnected ping");
+    socket
+        .send(&ping_packet())
+        .await
+        .into_diagnostic()
+        .wrap_err("Failed to send unconnected ping")?;
+
+    let mut buf = [0u8; 4096];
+    let len = socket
+        .recv(&mut buf)
+        .await
+        .into_diagnostic()
+        .wrap_err("Failed to receive unconnected pong")?;
+
+    parse_response(&buf[..len])
+}
+
+fn ping_packet() -> Vec<u8> {
+    // the client's time, which the server sends back
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+
+    let mut packet = vec![0x01];
+    packet.extend_from_slice(&time.to_be_bytes());
+    packet.extend_from_slice(&MAGIC);
+    packet.extend_from_slice(&CLIENT_GUID.to_be_bytes());
+    packet
+}
+
+fn parse_response(data: &[u8]) -> miette::Result<BedrockStatus> {
+    if data.first() != Some(&0x1c) {
+        bail!("Server sent an invalid unconnected pong packet");
+    }
+
+    // packet ID, time, server GUID and magic are followed by the length of the
+    // server ID string
+    let len = data
+        .get(33..35)
+        .map(|l| u16::from_be_bytes([l[0], l[1]]) as usize)
+        .ok_or_else(|| miette!("Unconnected pong packet is too short"))?;
+    let server_id = data
+        .get(35..35 + len)
+        .ok_or_else(|| miette!("Unconnected pong packet is too short"))?;
+    let server_id = String::from_utf8_lossy(server_id);
+
+    // edition;motd;protocol;version;online;max;server GUID;level name;game mode;...
+    let fields = server_id.split(';').collect::<Vec<_>>();
+    if fields.len() < 6 {
+        bail!(
+            "Unconnected pong has {} fields, expected at least 6",
+            fields.len()
+        );
+    }
+
+    Ok(BedrockStatus {
+        edition: fields[0].to_string(),
+        motd: fields[1].to_string(),
+        protocol: parse_number(fields[2], "protocol version")?,
+        version: fields[3].to_string(),
+        online: parse_number(fields[4], "player count")?,
+        max: parse_number(fields[5], "max player count")?,
+        level_name: fields.get(7).map(|s| s.to_string()),
+        game_mode: fields.get(8).map(|s| s.to_string()),
+    })
+}
+
+fn parse_number(s: &str, name: &str) -> miette::Result<u32> {
+    s.parse()
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Unconnected pong has invalid {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// an unconnected pong packet with the given server ID string
+    fn pong(server_id: &str) -> Vec<u8> {
+        let mut packet = vec![0x1c];
+        // time and server GUID
+        packet.extend_from_slice(&[0; 16]);
+        packet.extend_from_slice(&MAGIC);
+        packet.extend_from_slice(&(server_id.len() as u16).to_be_bytes());
+        packet.extend_from_slice(server_id.as_bytes());
+        packet
+    }
+
+    #[test]
+    fn parses_response() {
+        let data = pong("MCPE;A Server;589;1.20.0;3;10;1234;Bedrock level;Survival;1;19132;19133;");
+        let status = parse_response(&data).unwrap();
+
+        assert_eq!(status.edition, "MCPE");
+        assert_eq!(status.motd, "A Server");
+        assert_eq!(status.protocol, 589);
+        assert_eq!(status.version, "1.20.0");
+        assert_eq!(status.online, 3);
+        assert_eq!(status.max, 10);
+        assert_eq!(status.level_name.as_deref(), Some("Bedrock level"));
+        assert_eq!(status.game_mode.as_deref(), Some("Survival"));
+    }
+
+    #[test]
+    fn parses_response_without_optional_fields() {
+        let status = parse_response(&pong("MCPE;A Server;589;1.20.0;3;10")).unwrap();
+        assert_eq!(status.level_name, None);
+        assert_eq!(status.game_mode, None);
+    }
+
+    #[test]
+    fn rejects_truncated_response() {
+        let data = pong("MCPE;A Server;589;1.20.0;3;10");
+        assert!(parse_response(&[]).is_err());
+        assert!(parse_response(&data[..20]).is_err());
+        assert!(parse_response(&data[..data.len() - 1]).is_err());
+        assert!(parse_response(&pong("MCPE;A Server;589")).is_err());
+    }
+
+    #[test]
+    fn rejects_non_numeric_fields() {
+        assert!(parse_response(&pong("MCPE;A Server;589;1.20.0;three;10")).is_err());
+        assert!(parse_response(&pong("MCPE;A Server;new;1.20.0;3;10")).is_err());
+    }
+
+    #[test]
+    fn rejects_other_packets() {
+        let mut data = pong("MCPE;A Server;589;1.20.0;3;10");
+        data[0] = 0x01;
+        assert!(parse_response(&data).is_err());
+    }
+}
@@ -0,0 +1,108 @@
+//! The config file, which sets defaults for the command line options.
+
+use clap::{Arg, Command};
+use miette::{bail, IntoDiagnostic, WrapErr};
+use serde::Deserialize;
+use std::{collections::BTreeMap, env, ffi::OsString, fs, io, path::PathBuf};
+use toml::Value;
+
+/// the contents of the config file. It is read from
+/// `$XDG_CONFIG_HOME/mcstat/config.toml` by default, which can be overridden
+/// using the `MCSTAT_CONFIG` environment variable.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// default values of the command line options, by their long name
+    pub defaults: BTreeMap<String, Value>,
+    /// addresses of servers by name, so they can be pinged using their name
+    pub servers: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// loads the config file, returning an empty config if there is none
+    pub fn load() -> miette::Result<Self> {
+        let path = match path() {
+            Some(path) => path,
+            None => return Ok(Self::default()),
+        };
+
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(e)
+                    .into_diagnostic()
+                    .wrap_err_with(|| format!("Failed to read config file {}", path.display()))
+            },
+        };
+
+        toml::from_str(&content)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Invalid config file {}", path.display()))
+    }
+
+    /// returns the defaults as command line arguments of `cmd`. Defaults of the
+    /// `given` arguments and of those conflicting with one of them are left
+    /// out, so the command line takes precedence.
+    pub fn args(&self, cmd: &Command, given: &[&Arg]) -> miette::Result<Vec<OsString>> {
+        let conflicts = |a: &Arg, b: &Arg| {
+            cmd.get_arg_conflicts_with(a)
+                .iter()
+                .any(|c| c.get_id() == b.get_id())
+        };
+
+        let mut args = vec![];
+        for (name, value) in &self.defaults {
+            let arg = match cmd
+                .get_arguments()
+                .find(|arg| arg.get_long() == Some(name.as_str()))
+            {
+                Some(arg) => arg,
+                None => bail!(
+                    "The config file sets a default for the unknown option {}",
+                    name
+                ),
+            };
+            let overridden = given.iter().any(|&other| {
+                other.get_id() == arg.get_id() || conflicts(other, arg) || conflicts(arg, other)
+            });
+            if overridden {
+                continue;
+            }
+
+            let arg = match value {
+                Value::Boolean(true) => format!("--{}", name),
+                Value::Boolean(false) => continue,
+                Value::String(s) => format!("--{}={}", name, s),
+                Value::Integer(i) => format!("--{}={}", name, i),
+                Value::Float(f) => format!("--{}={}", name, f),
+                _ => bail!(
+                    "The default of {} in the config file has an invalid type",
+                    name
+                ),
+            };
+            args.push(arg.into());
+        }
+
+        Ok(args)
+    }
+
+    /// returns the address of the server with the given name, or `addr` itself
+    /// if it isn't a name of a server
+    pub fn resolve_alias<'a>(&'a self, addr: &'a str) -> &'a str {
+        self.servers.get(addr).map_or(addr, String::as_str)
+    }
+}
+
+/// the path of the config file, if it can be determined
+fn path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("MCSTAT_CONFIG") {
+        return Some(path.into());
+    }
+
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(dir.join("mcstat").join("config.toml"))
+}
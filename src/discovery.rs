@@ -0,0 +1,75 @@
+//! Discovery of worlds opened to LAN, which are announced by the hosting client
+//! via UDP multicast.
+
+use miette::{IntoDiagnostic, WrapErr};
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+use tokio::{
+    net::UdpSocket,
+    time::{self, Instant},
+};
+use tracing::info;
+
+const MULTICAST_ADDR: Ipv4Addr = Ipv4Addr::new(224, 0, 2, 60);
+const PORT: u16 = 4445;
+
+/// a world opened to LAN
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LanServer {
+    pub addr: SocketAddr,
+    /// the MOTD, which is the name of the world
+    pub motd: String,
+}
+
+/// listens for announcements of LAN worlds for `duration`, returning each
+/// world that was found once
+pub async fn discover(duration: Duration) -> miette::Result<Vec<LanServer>> {
+    info!("Listening for LAN worlds on {}:{}", MULTICAST_ADDR, PORT);
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, PORT))
+        .await
+        .into_diagnostic()
+        .wrap_err("Failed to bind UDP socket for LAN discovery")?;
+    socket
+        .join_multicast_v4(MULTICAST_ADDR, Ipv4Addr::UNSPECIFIED)
+        .into_diagnostic()
+        .wrap_err("Failed to join LAN discovery multicast group")?;
+
+    let deadline = Instant::now() + duration;
+    let mut servers = Vec::<LanServer>::new();
+    let mut buf = [0u8; 1024];
+    while let Ok(received) = time::timeout_at(deadline, socket.recv_from(&mut buf)).await {
+        let (len, from) = received.into_diagnostic()?;
+        let msg = String::from_utf8_lossy(&buf[..len]);
+
+        match parse_announcement(&msg, from.ip()) {
+            Some(server) if !servers.iter().any(|s| s.addr == server.addr) => {
+                info!("Found LAN world at {}", server.addr);
+                servers.push(server);
+            },
+            Some(_) => {},
+            None => info!("Ignoring invalid LAN announcement from {}", from),
+        }
+    }
+
+    Ok(servers)
+}
+
+/// parses an announcement in the format `[MOTD]motd[/MOTD][AD]port[/AD]`
+fn parse_announcement(msg: &str, ip: IpAddr) -> Option<LanServer> {
+    let motd = between(msg, "[MOTD]", "[/MOTD]")?;
+    // old versions send the address along with the port
+    let port = between(msg, "[AD]", "[/AD]")?.rsplit(':').next()?;
+
+    Some(LanServer {
+        addr: SocketAddr::new(ip, port.parse().ok()?),
+        motd: motd.to_string(),
+    })
+}
+
+fn between<'a>(s: &'a str, start: &str, end: &str) -> Option<&'a str> {
+    let (_, rest) = s.split_once(start)?;
+    let (inner, _) = rest.split_once(end)?;
+    Some(inner)
+}
@@ -0,0 +1,190 @@
+//! Status request of minecraft 1.7 and newer. This works on any stream, so the
+//! connection to the server can be established in different ways.
+
+use miette::{bail, IntoDiagnostic, WrapErr};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    time::Instant,
+};
+
+/// the largest packet length a server may send
+const MAX_PACKET_LEN: usize = 2097151;
+
+/// sends the handshake switching the connection to the status state. This must
+/// be sent before `status` or `ping`.
+pub async fn handshake<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    addr: &str,
+    port: u16,
+    protocol_version: i32,
+) -> miette::Result<()> {
+    let mut data = vec![];
+    write_varint(&mut data, protocol_version);
+    write_string(&mut data, addr);
+    data.extend_from_slice(&port.to_be_bytes());
+    // next state: status
+    write_varint(&mut data, 1);
+
+    write_packet(stream, 0x00, &data)
+        .await
+        .wrap_err("Failed to send handshake")
+}
+
+/// requests the status of the server, returning the raw JSON response
+pub async fn status<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut S) -> miette::Result<String> {
+    write_packet(stream, 0x00, &[])
+        .await
+        .wrap_err("Failed to send status request")?;
+
+    let (id, data) = read_packet(stream)
+        .await
+        .wrap_err("Failed to read status response")?;
+    if id != 0x00 {
+        bail!("Status response has invalid packet ID {:#x}", id);
+    }
+
+    let mut data = &data[..];
+    let len = read_varint(&mut data).await? as usize;
+    if len > data.len() {
+        bail!("Status response is truncated");
+    }
+
+    Ok(decode_lossy(&data[..len]))
+}
+
+/// decodes UTF-8, treating invalid bytes as Latin-1. Some servers send a raw
+/// `0xA7` byte as the section sign of formatting codes, which would otherwise
+/// be lost. Invalid responses are kept this way, so they can be shown to the
+/// user.
+fn decode_lossy(mut bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len());
+    loop {
+        match std::str::from_utf8(bytes) {
+            Ok(s) => {
+                out.push_str(s);
+                return out;
+            },
+            Err(e) => {
+                let (valid, rest) = bytes.split_at(e.valid_up_to());
+                // this can't fail, since the bytes were just validated
+                out.push_str(std::str::from_utf8(valid).unwrap_or_default());
+
+                // Latin-1 bytes map directly to the first 256 code points
+                let invalid = e.error_len().unwrap_or(rest.len());
+                out.extend(rest[..invalid].iter().map(|&b| char::from(b)));
+                bytes = &rest[invalid..];
+            },
+        }
+    }
+}
+
+/// sends a ping to the server and waits for the response, returning the time
+/// it took
+pub async fn ping<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut S) -> miette::Result<Duration> {
+    let payload = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64;
+
+    let start = Instant::now();
+    write_packet(stream, 0x01, &payload.to_be_bytes())
+        .await
+        .wrap_err("Failed to send ping")?;
+
+    let (id, data) = read_packet(stream).await.wrap_err("Failed to read pong")?;
+    let elapsed = start.elapsed();
+
+    if id != 0x01 || data != payload.to_be_bytes() {
+        bail!("Server sent an invalid pong");
+    }
+
+    Ok(elapsed)
+}
+
+fn write_varint(buf: &mut Vec<u8>, value: i32) {
+    let mut value = value as u32;
+    loop {
+        if value & !0x7f == 0 {
+            buf.push(value as u8);
+            return;
+        }
+
+        buf.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+}
+
+async fn read_varint<S: AsyncRead + Unpin>(stream: &mut S) -> miette::Result<i32> {
+    let mut value = 0u32;
+    for i in 0..5 {
+        let byte = stream.read_u8().await.into_diagnostic()?;
+        value |= u32::from(byte & 0x7f) << (7 * i);
+
+        if byte & 0x80 == 0 {
+            return Ok(value as i32);
+        }
+    }
+
+    bail!("VarInt is too long");
+}
+
+fn write_string(buf: &mut Vec<u8>, s: &str) {
+    write_varint(buf, s.len() as i32);
+    buf.extend_from_slice(s.as_bytes());
+}
+
+/// writes a packet with the given ID, prefixed with its length
+async fn write_packet<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    id: i32,
+    data: &[u8],
+) -> miette::Result<()> {
+    let mut body = vec![];
+    write_varint(&mut body, id);
+    body.extend_from_slice(data);
+
+    let mut packet = vec![];
+    write_varint(&mut packet, body.len() as i32);
+    packet.extend_from_slice(&body);
+
+    stream.write_all(&packet).await.into_diagnostic()
+}
+
+/// reads a packet, returning its ID and data
+async fn read_packet<S: AsyncRead + Unpin>(stream: &mut S) -> miette::Result<(i32, Vec<u8>)> {
+    let len = read_varint(stream).await?;
+    if !(1..=MAX_PACKET_LEN as i32).contains(&len) {
+        bail!(
+            "Server sent a packet with invalid length {}. It may not be a minecraft server",
+            len
+        );
+    }
+
+    let mut body = vec![0u8; len as usize];
+    stream.read_exact(&mut body).await.into_diagnostic()?;
+
+    let mut data = &body[..];
+    let id = read_varint(&mut data).await?;
+    Ok((id, data.to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_lossy_raw_section_sign() {
+        assert_eq!(decode_lossy(b"\xa7aGreen"), "§aGreen");
+    }
+
+    #[test]
+    fn decode_lossy_mixed() {
+        assert_eq!(
+            decode_lossy(b"Gr\xc3\xbc\xc3\x9fe \xa7cRed \xff!"),
+            "Grüße §cRed ÿ!"
+        );
+        // an incomplete character at the end
+        assert_eq!(decode_lossy(b"ab\xe4\xb8"), "ab\u{e4}\u{b8}");
+    }
+}
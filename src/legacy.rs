@@ -0,0 +1,201 @@
+//! Server list ping used by servers older than minecraft 1.7, which don't
+//! understand the modern status request.
+
+use miette::{bail, miette, IntoDiagnostic, WrapErr};
+use serde_json::{json, Value};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::info;
+
+/// the protocol version sent with the ping, this is 1.6.4
+const PROTOCOL_VERSION: u8 = 78;
+
+/// response to a legacy server list ping
+pub struct LegacyStatus {
+    /// not sent by servers older than 1.4
+    pub protocol: Option<u32>,
+    /// not sent by servers older than 1.4
+    pub version: Option<String>,
+    pub motd: String,
+    pub online: u32,
+    pub max: u32,
+}
+
+impl LegacyStatus {
+    /// converts this status to the format of a modern status response, so it
+    /// can be handled the same way
+    pub fn to_json(&self) -> Value {
+        json!({
+            "version": {
+                "name": self.version.as_deref().unwrap_or_default(),
+                "protocol": self.protocol.unwrap_or_default(),
+            },
+            "players": {
+                "online": self.online,
+                "max": self.max,
+            },
+            "description": {
+                "text": self.motd,
+                "extra": [],
+            },
+        })
+    }
+}
+
+/// sends a legacy server list ping over a connection to the server at `addr`
+/// and parses the response
+pub async fn ping<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    addr: &str,
+    port: u16,
+) -> miette::Result<LegacyStatus> {
+    info!("Sending legacy ping");
+    stream
+        .write_all(&ping_packet(addr, port))
+        .await
+        .into_diagnostic()
+        .wrap_err("Failed to send legacy ping")?;
+
+    // the response is a kick packet with the ID 0xff, followed by the length of the
+    // message in characters
+    let mut header = [0u8; 3];
+    stream
+        .read_exact(&mut header)
+        .await
+        .into_diagnostic()
+        .wrap_err("Failed to read legacy ping response")?;
+
+    if header[0] != 0xff {
+        bail!(
+            "Legacy ping response has invalid packet ID {:#x}",
+            header[0]
+        );
+    }
+
+    let len = u16::from_be_bytes([header[1], header[2]]) as usize;
+    let mut data = vec![0u8; len * 2];
+    stream
+        .read_exact(&mut data)
+        .await
+        .into_diagnostic()
+        .wrap_err("Failed to read legacy ping response")?;
+
+    let text = String::from_utf16(
+        &data
+            .chunks_exact(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .collect::<Vec<_>>(),
+    )
+    .into_diagnostic()
+    .wrap_err("Legacy ping response is not valid UTF-16")?;
+
+    parse_response(&text)
+}
+
+/// builds the ping packet, including the `MC|PingHost` plugin message sent by
+/// 1.6 clients
+fn ping_packet(addr: &str, port: u16) -> Vec<u8> {
+    let mut packet = vec![0xfe, 0x01, 0xfa];
+    push_string(&mut packet, "MC|PingHost");
+
+    // length of the remaining data
+    let host_len = addr.encode_utf16().count() as u16;
+    packet.extend_from_slice(&(7 + host_len * 2).to_be_bytes());
+
+    packet.push(PROTOCOL_VERSION);
+    push_string(&mut packet, addr);
+    packet.extend_from_slice(&i32::from(port).to_be_bytes());
+    packet
+}
+
+/// appends `s` as UTF-16 prefixed with its length in characters
+fn push_string(buf: &mut Vec<u8>, s: &str) {
+    let chars = s.encode_utf16().collect::<Vec<_>>();
+    buf.extend_from_slice(&(chars.len() as u16).to_be_bytes());
+    for c in chars {
+        buf.extend_from_slice(&c.to_be_bytes());
+    }
+}
+
+fn parse_response(text: &str) -> miette::Result<LegacyStatus> {
+    if let Some(fields) = text.strip_prefix("§1\0") {
+        // 1.4+ format: §1\0protocol\0version\0motd\0online\0max
+        let fields = fields.split('\0').collect::<Vec<_>>();
+        if let [protocol, version, motd, online, max] = fields[..] {
+            Ok(LegacyStatus {
+                protocol: Some(parse_number(protocol, "protocol version")?),
+                version: Some(version.to_string()),
+                motd: motd.to_string(),
+                online: parse_number(online, "player count")?,
+                max: parse_number(max, "max player count")?,
+            })
+        } else {
+            bail!(
+                "Legacy ping response has {} fields, expected 5",
+                fields.len()
+            );
+        }
+    } else {
+        // pre 1.4 format: motd§online§max
+        let mut fields = text.rsplitn(3, '§');
+        let max = fields.next();
+        let online = fields.next();
+        let motd = fields.next();
+        let (motd, online, max) = motd
+            .zip(online)
+            .zip(max)
+            .map(|((m, o), x)| (m, o, x))
+            .ok_or_else(|| miette!("Legacy ping response has invalid format"))?;
+
+        Ok(LegacyStatus {
+            protocol: None,
+            version: None,
+            motd: motd.to_string(),
+            online: parse_number(online, "player count")?,
+            max: parse_number(max, "max player count")?,
+        })
+    }
+}
+
+fn parse_number(s: &str, name: &str) -> miette::Result<u32> {
+    s.parse()
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Legacy ping response has invalid {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_response() {
+        let status = parse_response("§1\u{0}127\u{0}1.6.4\u{0}A §aServer\u{0}5\u{0}20").unwrap();
+        assert_eq!(status.protocol, Some(127));
+        assert_eq!(status.version.as_deref(), Some("1.6.4"));
+        assert_eq!(status.motd, "A §aServer");
+        assert_eq!(status.online, 5);
+        assert_eq!(status.max, 20);
+    }
+
+    #[test]
+    fn parses_pre_1_4_response() {
+        let status = parse_response("A §aServer§5§20").unwrap();
+        assert_eq!(status.protocol, None);
+        assert_eq!(status.motd, "A §aServer");
+        assert_eq!(status.online, 5);
+        assert_eq!(status.max, 20);
+    }
+
+    #[test]
+    fn rejects_truncated_response() {
+        assert!(parse_response("").is_err());
+        assert!(parse_response("§1\u{0}127\u{0}1.6.4").is_err());
+        assert!(parse_response("A Server§5").is_err());
+    }
+
+    #[test]
+    fn rejects_non_numeric_fields() {
+        assert!(parse_response("§1\u{0}127\u{0}1.6.4\u{0}motd\u{0}five\u{0}20").is_err());
+        assert!(parse_response("§1\u{0}new\u{0}1.6.4\u{0}motd\u{0}5\u{0}20").is_err());
+        assert!(parse_response("A Server§5§-1").is_err());
+    }
+}
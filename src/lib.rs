@@ -2,13 +2,43 @@ use crate::output::Table;
 use async_minecraft_ping::StatusResponse;
 use image::{DynamicImage, ImageFormat};
 use itertools::Itertools;
-use miette::{bail, miette, IntoDiagnostic, WrapErr};
+use miette::{bail, miette, Diagnostic, IntoDiagnostic, WrapErr};
 use serde::Deserialize;
-use std::{io::Cursor, net::IpAddr};
+use serde_json::{json, Map, Value};
+use smart_default::SmartDefault;
+use socks::Proxy;
+use std::{
+    borrow::Cow,
+    fmt,
+    future::Future,
+    io::{self, Cursor},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{lookup_host, TcpSocket, TcpStream, ToSocketAddrs, UdpSocket},
+    time::{self, Instant},
+};
 use tracing::info;
-use trust_dns_resolver::TokioAsyncResolver;
+use trust_dns_resolver::{
+    config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
+    TokioAsyncResolver,
+};
+use unicode_width::UnicodeWidthStr;
 
+pub mod bedrock;
+pub mod discovery;
+pub mod java;
+pub mod legacy;
 pub mod output;
+pub mod protocol_versions;
+pub mod query;
+pub mod socks;
+#[cfg(feature = "tls")]
+pub mod tls;
 
 /// returns an `Option` of the expression passed in
 /// `None` if the `is_empty` on the expression returns true, `Some(x)` otherwise
@@ -34,38 +64,152 @@ pub enum EitherStatusResponse {
     Normal(StatusResponse),
 }
 
-pub async fn resolve_address(addr_and_port: &str) -> miette::Result<(String, u16)> {
-    info!("Resolving address");
-    let addr;
-    let port;
-    if let Some((addr_, port_)) = addr_and_port.split_once(':') {
+/// splits an address into the host and the port, if one is given. IPv6
+/// addresses must be enclosed in brackets if a port is given. A leading
+/// `minecraft://` or `tcp://` and trailing slashes are ignored.
+fn split_port(addr_and_port: &str) -> miette::Result<(&str, Option<u16>)> {
+    let addr_and_port = strip_scheme(addr_and_port)?;
+    if let Some(rest) = addr_and_port.strip_prefix('[') {
+        let (addr, rest) = rest
+            .split_once(']')
+            .ok_or_else(|| miette!("IPv6 address is missing a closing bracket"))?;
+
+        if rest.is_empty() {
+            info!("Bracketed address has no explicit port");
+            return Ok((addr, None));
+        }
+
+        let port = rest
+            .strip_prefix(':')
+            .ok_or_else(|| miette!("Expected a port after the IPv6 address"))?;
+        info!("Bracketed address has explicit port");
+        let port = port
+            .parse()
+            .into_diagnostic()
+            .wrap_err("User provided port is invalid")?;
+        Ok((addr, Some(port)))
+    } else if addr_and_port.parse::<Ipv6Addr>().is_ok() {
+        info!("Address is an IPv6 address without port");
+        Ok((addr_and_port, None))
+    } else if let Some((addr, port)) = addr_and_port.split_once(':') {
         info!("Address has explicit port");
-        addr = addr_;
-        port = Some(
-            port_
-                .parse()
-                .into_diagnostic()
-                .wrap_err("User provided port is invalid")?,
-        );
+        let port = port
+            .parse()
+            .into_diagnostic()
+            .wrap_err("User provided port is invalid")?;
+        Ok((addr, Some(port)))
     } else {
         info!("Address has no explicit port");
-        addr = addr_and_port;
-        port = None;
+        Ok((addr_and_port, None))
     }
+}
+
+/// the way an address was resolved by `resolve_address`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolveMethod {
+    /// the port was given explicitly, so no lookup was made
+    ExplicitPort,
+    /// an IP address was given without a port, so the default port was used
+    IpAddress,
+    /// the host and port were taken from a SRV record
+    Srv,
+    /// no SRV record was found, so the default port was used
+    DefaultPort,
+    /// SRV lookups were disabled, so the default port was used
+    SrvDisabled,
+    /// the server was connected to through a unix socket, so no lookup was
+    /// made. The host and port are only sent in the handshake.
+    UnixSocket,
+}
+
+impl fmt::Display for ResolveMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::ExplicitPort => "explicit port",
+            Self::IpAddress => "IP address with default port",
+            Self::Srv => "SRV record",
+            Self::DefaultPort => "no SRV record, default port",
+            Self::SrvDisabled => "SRV lookup disabled, default port",
+            Self::UnixSocket => "unix socket, no lookup",
+        })
+    }
+}
+
+/// a host and port resolved by `resolve_address`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedAddress {
+    pub host: String,
+    pub port: u16,
+    pub method: ResolveMethod,
+}
+
+impl fmt::Display for ResolvedAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.host.parse::<Ipv6Addr>().is_ok() {
+            write!(f, "[{}]:{}", self.host, self.port)
+        } else {
+            write!(f, "{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// removes a `minecraft://` or `tcp://` scheme and trailing slashes, which are
+/// sometimes pasted along with an address
+fn strip_scheme(addr: &str) -> miette::Result<&str> {
+    let addr = match addr.split_once("://") {
+        Some((scheme, rest))
+            if scheme.eq_ignore_ascii_case("minecraft") || scheme.eq_ignore_ascii_case("tcp") =>
+        {
+            rest
+        },
+        Some((scheme, _)) => bail!("Unsupported address scheme {}://", scheme),
+        None => addr,
+    };
+    Ok(addr.trim_end_matches('/'))
+}
+
+/// resolves the host and port of a server from an address in the format
+/// `host[:port]`. IPv6 addresses may be given as-is or in brackets, as in
+/// `[::1]:25565`. The address may start with `minecraft://` or `tcp://`.
+///
+/// - if a port is given, it is used as-is and no SRV lookup is made
+/// - if the host is an IP address without a port, the default port 25565 is
+///   used
+/// - if `srv` is false, the default port 25565 is used
+/// - otherwise, the `_minecraft._tcp` SRV record of the host is looked up. If
+///   there is none, the host is used with the default port 25565.
+///
+/// DNS queries are sent to `nameserver` if given, or the system's resolver
+/// otherwise.
+///
+/// returns the host, the port and the way they were resolved.
+pub async fn resolve_address(
+    addr_and_port: &str,
+    srv: bool,
+    nameserver: Option<IpAddr>,
+) -> miette::Result<ResolvedAddress> {
+    info!("Resolving address");
+    let (addr, port) = split_port(addr_and_port)?;
+
+    let resolved = |host: &str, port, method| ResolvedAddress {
+        host: host.to_string(),
+        port,
+        method,
+    };
 
     if let Some(port) = port {
-        Ok((addr.to_string(), port))
+        Ok(resolved(addr, port, ResolveMethod::ExplicitPort))
     } else if addr.parse::<IpAddr>().is_ok() {
         info!("Got IP address without explicit port, assuming 25565");
         // if we only have an IP and no port, there is no domain to lookup so we can
         // only default to port 25565.
-        Ok((addr.to_string(), 25565))
+        Ok(resolved(addr, 25565, ResolveMethod::IpAddress))
+    } else if !srv {
+        info!("SRV lookup disabled, using port 25565");
+        Ok(resolved(addr, 25565, ResolveMethod::SrvDisabled))
     } else {
         info!("Sending SRV request");
-        let dns = TokioAsyncResolver::tokio_from_system_conf()
-            .into_diagnostic()
-            .wrap_err("Failed to create DNS resolver")?;
-
+        let dns = dns_resolver(nameserver)?;
         let lookup = dns.srv_lookup(format!("_minecraft._tcp.{}.", addr)).await;
 
         if let Ok(lookup) = lookup {
@@ -80,55 +224,732 @@ pub async fn resolve_address(addr_and_port: &str) -> miette::Result<(String, u16
 
             let port = srv.port();
 
-            Ok((addr.to_string(), port))
+            Ok(resolved(addr, port, ResolveMethod::Srv))
         } else {
             info!("No SRV record found. Defaulting to 25565");
             // if there is no SRV record, we have to default to port 25565
-            Ok((addr.to_string(), 25565))
+            Ok(resolved(addr, 25565, ResolveMethod::DefaultPort))
         }
     }
 }
 
-/// formats a iterator to a readable list
+/// creates a DNS resolver using `nameserver`, or the system configuration if
+/// it is `None`
+fn dns_resolver(nameserver: Option<IpAddr>) -> miette::Result<TokioAsyncResolver> {
+    let dns = match nameserver {
+        Some(ip) => {
+            let servers = NameServerConfigGroup::from_ips_clear(&[ip], 53, true);
+            TokioAsyncResolver::tokio(
+                ResolverConfig::from_parts(None, vec![], servers),
+                ResolverOpts::default(),
+            )
+        },
+        None => TokioAsyncResolver::tokio_from_system_conf(),
+    };
+    dns.into_diagnostic()
+        .wrap_err("Failed to create DNS resolver")
+}
+
+/// error returned if an operation, like connecting to a server, doesn't
+/// complete in time. Contains a description of the operation.
+#[derive(Debug)]
+pub struct TimeoutError(pub &'static str);
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} timed out.", self.0)
+    }
+}
+
+impl std::error::Error for TimeoutError {}
+
+impl Diagnostic for TimeoutError {}
+
+/// errors of requesting and parsing a status, which can be told apart using
+/// `downcast_ref` on the returned reports
+#[derive(Debug)]
+pub enum StatusError {
+    /// resolving the address of the server failed
+    Resolve,
+    /// the response is not a valid status. Contains the start of it.
+    InvalidStatus(String),
+    /// the server responded with a plain text message instead of its status
+    ServerMessage(String),
+}
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Resolve => f.write_str("Error resolving address"),
+            Self::InvalidStatus(preview) => write!(
+                f,
+                "Received an invalid status response: {:?}\nThe address may not belong to a \
+                 minecraft server.",
+                preview
+            ),
+            Self::ServerMessage(text) => write!(f, "The server says: {}", text),
+        }
+    }
+}
+
+impl std::error::Error for StatusError {}
+
+impl Diagnostic for StatusError {}
+
+/// the protocol version sent in the handshake of a status request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtocolVersion {
+    /// protocol version -1, which servers treat as a version query and usually
+    /// answer regardless of the version they run
+    Auto,
+    /// a version number, which is never negative
+    Number(i32),
+}
+
+impl ProtocolVersion {
+    /// the value sent in the handshake
+    fn handshake_value(self) -> i32 {
+        match self {
+            Self::Auto => -1,
+            Self::Number(n) => n,
+        }
+    }
+}
+
+impl FromStr for ProtocolVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" | "-1" => Ok(Self::Auto),
+            // the version is sent as a signed VarInt
+            _ => s
+                .parse::<i32>()
+                .ok()
+                .filter(|&n| n >= 0)
+                .map(Self::Number)
+                .ok_or_else(|| {
+                    format!(
+                        "invalid protocol version `{}`, expected `auto` or a number up to {}",
+                        s,
+                        i32::MAX
+                    )
+                }),
+        }
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auto => f.write_str("auto"),
+            Self::Number(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+/// options for fetching the status of a server
+#[derive(Debug, Clone, SmartDefault)]
+pub struct FetchOptions {
+    /// the protocol version sent in the handshake
+    #[default(ProtocolVersion::Number(751))]
+    pub protocol_version: ProtocolVersion,
+    /// the time before the connection to the server times out
+    #[default(Duration::from_millis(5000))]
+    pub timeout: Duration,
+    /// the time before resolving the address of the server times out
+    #[default(Duration::from_millis(5000))]
+    pub resolve_timeout: Duration,
+    /// only use the legacy server list ping of servers older than 1.7
+    pub legacy: bool,
+    /// ping a bedrock edition server instead of a java edition one
+    pub bedrock: bool,
+    /// the number of times to measure the ping. At least one sample is always
+    /// taken.
+    #[default(1)]
+    pub ping_count: usize,
+    /// the SOCKS5 proxy to connect through. Only used for java edition servers.
+    pub proxy: Option<Proxy>,
+    /// the local address to connect from
+    pub bind: Option<IpAddr>,
+    /// the number of times fetching the status is retried if it fails
+    pub retries: usize,
+    /// don't look up SRV records, always using the default port if none is
+    /// given
+    pub no_srv: bool,
+    /// the path of a unix socket to connect to instead of the server. The
+    /// address is then only sent in the handshake and not resolved. Only
+    /// supported on unix.
+    pub unix: Option<PathBuf>,
+    /// wrap the connection to java edition servers in TLS. Requires the `tls`
+    /// feature.
+    pub tls: Option<TlsOptions>,
+    /// the DNS server to look up SRV records and the addresses of java
+    /// edition servers with, instead of the system's resolver
+    pub resolver: Option<IpAddr>,
+}
+
+/// options for connecting to a server over TLS
+#[derive(Debug, Clone, Default)]
+pub struct TlsOptions {
+    /// the server name sent to the server and verified in its certificate.
+    /// Defaults to the address of the server.
+    pub sni: Option<String>,
+    /// accept any certificate
+    pub insecure: bool,
+}
+
+/// the time waited before the first retry of `fetch_raw_status`
+const RETRY_BACKOFF: Duration = Duration::from_millis(200);
+
+/// the unparsed status of a server returned by `fetch_raw_status`
+#[derive(Debug, Clone)]
+pub struct RawStatus {
+    /// the JSON status response
+    pub response: String,
+    /// the ping samples. For modern servers, this is the round trip of the
+    /// ping packet, which doesn't include connecting to the server. The legacy
+    /// and bedrock pings have no such packet, so the whole request is timed.
+    pub pings: Vec<Duration>,
+    /// the address the server was reached at. This is `None` for bedrock
+    /// servers, as their address isn't resolved.
+    pub address: Option<ResolvedAddress>,
+    /// how long connecting and requesting the status took. This is only
+    /// measured for the modern status request.
+    pub timing: Option<Timing>,
+}
+
+/// the durations of the stages of a status request
+#[derive(Debug, Clone, Copy)]
+pub struct Timing {
+    /// the time it took to establish the TCP connection
+    pub connect: Duration,
+    /// the time from the handshake until the status response was received
+    pub status: Duration,
+}
+
+/// resolves the address and requests the status of the server, returning the
+/// raw JSON response, the ping samples and the resolved address.
+///
+/// `addr` is resolved using `resolve_address`. If the status request fails, a
+/// legacy ping is attempted.
+///
+/// For bedrock servers, no SRV lookup is made and the port defaults to 19132.
+///
+/// If this fails, it is retried as often as set in the options, waiting twice
+/// as long before each retry.
+pub async fn fetch_raw_status(addr: &str, options: &FetchOptions) -> miette::Result<RawStatus> {
+    let mut backoff = RETRY_BACKOFF;
+    let mut retry = 0;
+    loop {
+        match fetch_raw_status_once(addr, options).await {
+            Err(e) if retry < options.retries => {
+                retry += 1;
+                info!(
+                    "Fetching status failed, retry {} in {} ms: {:?}",
+                    retry,
+                    backoff.as_millis(),
+                    e
+                );
+                time::sleep(backoff).await;
+                backoff *= 2;
+            },
+            res => return res,
+        }
+    }
+}
+
+async fn fetch_raw_status_once(addr: &str, options: &FetchOptions) -> miette::Result<RawStatus> {
+    if options.bedrock {
+        let (addr, port) = split_port(addr)?;
+        let port = port.unwrap_or(bedrock::DEFAULT_PORT);
+        info!("Using address '{}:{}'", addr, port);
+
+        let (status, pings) =
+            time_pings(options, || bedrock::ping(addr, port, options.bind)).await?;
+        return Ok(RawStatus {
+            response: status.to_json().to_string(),
+            pings,
+            address: None,
+            timing: None,
+        });
+    }
+
+    let address = if options.unix.is_some() {
+        let (host, port) = split_port(addr)?;
+        ResolvedAddress {
+            host: host.to_string(),
+            port: port.unwrap_or(25565),
+            method: ResolveMethod::UnixSocket,
+        }
+    } else {
+        time::timeout(
+            options.resolve_timeout,
+            resolve_address(addr, !options.no_srv, options.resolver),
+        )
+        .await
+        .map_err(|_| TimeoutError("Resolving the address"))?
+        .wrap_err(StatusError::Resolve)?
+    };
+
+    info!("Using address '{}' ({})", address, address.method);
+    let (host, port) = (&address.host, address.port);
+
+    let (response, pings, timing) = if options.legacy {
+        let (response, pings) = fetch_legacy_status(host, port, options).await?;
+        (response, pings, None)
+    } else {
+        match fetch_modern_status(host, port, options).await {
+            Ok((response, pings, timing)) => (response, pings, Some(timing)),
+            Err(e) => {
+                info!("Status request failed, trying legacy ping: {:?}", e);
+                // report the original error if the server doesn't understand the legacy
+                // ping either
+                let (response, pings) = fetch_legacy_status(host, port, options)
+                    .await
+                    .map_err(|_| e)?;
+                (response, pings, None)
+            },
+        }
+    };
+
+    Ok(RawStatus {
+        response,
+        pings,
+        address: Some(address),
+        timing,
+    })
+}
+
+/// the number of characters of an invalid status response included in errors
+const PREVIEW_LEN: usize = 80;
+
+/// parses a raw status response, returning it as a JSON value and as a status.
+///
+/// if the response is invalid, the error contains the start of it, since the
+/// server likely isn't a minecraft server.
+pub fn parse_status(raw: &str) -> miette::Result<(Value, EitherStatusResponse)> {
+    let context = || {
+        let mut preview = raw.chars().take(PREVIEW_LEN).collect::<String>();
+        if preview.len() < raw.len() {
+            preview.push_str("...");
+        }
+
+        StatusError::InvalidStatus(preview)
+    };
+
+    let json = serde_json::from_str::<Value>(raw)
+        .into_diagnostic()
+        .wrap_err_with(context)?;
+
+    // some servers send the description as a plain string or an array of
+    // components, which the status type only accepts as an object.
+    let mut normalized = json.clone();
+    if let Some(description) = normalized.get_mut("description") {
+        if !description.is_object() {
+            *description = json!({
+                "text": chat_component_to_mc_formatted(description),
+            });
+        }
+    }
+
+    // negative player counts can't be parsed by the status type, so they are
+    // clamped to 0. The actual values are still in the returned JSON.
+    if let Some(players) = normalized.get_mut("players").and_then(Value::as_object_mut) {
+        for key in ["online", "max"] {
+            if let Some(count) = players
+                .get_mut(key)
+                .filter(|c| c.as_i64().map_or(false, |n| n < 0))
+            {
+                *count = json!(0);
+            }
+        }
+    }
+
+    let status = serde_json::from_value(normalized)
+        .into_diagnostic()
+        .wrap_err_with(context)?;
+    Ok((json, status))
+}
+
+/// like `fetch_raw_status`, but parses the response.
 ///
-/// if `second_column`, the right strings will also be displayed
+/// fails if the server responds with a plain text message instead of a status.
+pub async fn fetch_status(
+    addr: &str,
+    options: &FetchOptions,
+) -> miette::Result<(StatusResponse, Vec<Duration>)> {
+    let raw = fetch_raw_status(addr, options).await?;
+
+    info!("Parsing status");
+    match parse_status(&raw.response)?.1 {
+        EitherStatusResponse::Text { text } => Err(StatusError::ServerMessage(text).into()),
+        EitherStatusResponse::Normal(r) => Ok((r, raw.pings)),
+    }
+}
+
+/// returns the minimum, average and maximum of the given ping samples
+pub fn ping_stats(pings: &[Duration]) -> Option<(Duration, Duration, Duration)> {
+    let min = *pings.iter().min()?;
+    let max = *pings.iter().max()?;
+    let avg = pings.iter().sum::<Duration>() / pings.len() as u32;
+    Some((min, avg, max))
+}
+
+async fn fetch_modern_status(
+    addr: &str,
+    port: u16,
+    options: &FetchOptions,
+) -> miette::Result<(String, Vec<Duration>, Timing)> {
+    let protocol_version = options.protocol_version.handshake_value();
+
+    let (status, ping, timing) = with_timeout(options.timeout, async {
+        let start = Instant::now();
+        let mut stream = connect(addr, port, options).await?;
+        let connect = start.elapsed();
+
+        let start = Instant::now();
+        java::handshake(&mut stream, addr, port, protocol_version).await?;
+        info!("Requesting status");
+        let status = java::status(&mut stream).await?;
+        let timing = Timing {
+            connect,
+            status: start.elapsed(),
+        };
+
+        let ping = java::ping(&mut stream).await?;
+
+        Result::<_, miette::Error>::Ok((status, ping, timing))
+    })
+    .await?;
+
+    let mut pings = vec![ping];
+    // the server closes the connection after a ping, so we need to reconnect for
+    // each sample
+    for i in 1..options.ping_count {
+        info!("Measuring ping sample {}", i + 1);
+        let ping = with_timeout(options.timeout, async {
+            let mut stream = connect(addr, port, options).await?;
+            java::handshake(&mut stream, addr, port, protocol_version).await?;
+            java::ping(&mut stream).await
+        })
+        .await?;
+        pings.push(ping);
+    }
+
+    Ok((status, pings, timing))
+}
+
+/// sends a legacy ping to the server, returning the response converted to the
+/// modern format and the ping samples
+async fn fetch_legacy_status(
+    addr: &str,
+    port: u16,
+    options: &FetchOptions,
+) -> miette::Result<(String, Vec<Duration>)> {
+    let (status, pings) = time_pings(options, || async {
+        let mut stream = connect(addr, port, options).await?;
+        legacy::ping(&mut stream, addr, port).await
+    })
+    .await?;
+    Ok((status.to_json().to_string(), pings))
+}
+
+/// runs `ping` as many times as there are ping samples requested, returning the
+/// result of the first run and the time each run took
+async fn time_pings<T, F>(
+    options: &FetchOptions,
+    ping: impl Fn() -> F,
+) -> miette::Result<(T, Vec<Duration>)>
+where
+    F: Future<Output = miette::Result<T>>,
+{
+    let start = Instant::now();
+    let status = with_timeout(options.timeout, ping()).await?;
+    let mut pings = vec![start.elapsed()];
+
+    for i in 1..options.ping_count {
+        info!("Measuring ping sample {}", i + 1);
+        let start = Instant::now();
+        with_timeout(options.timeout, ping()).await?;
+        pings.push(start.elapsed());
+    }
+
+    Ok((status, pings))
+}
+
+/// runs `fut`, failing with a `TimeoutError` if it takes longer than `timeout`
+async fn with_timeout<T>(
+    timeout: Duration,
+    fut: impl Future<Output = miette::Result<T>>,
+) -> miette::Result<T> {
+    time::timeout(timeout, fut)
+        .await
+        .map_err(|_| TimeoutError("Connection to server"))?
+}
+
+/// a connection to a server, which may be any kind of stream
+trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+/// opens a connection to the server, through the unix socket if one is set
+async fn connect(addr: &str, port: u16, options: &FetchOptions) -> miette::Result<Box<dyn Stream>> {
+    let stream = connect_plain(addr, port, options).await?;
+    match &options.tls {
+        #[cfg(feature = "tls")]
+        Some(tls) => Ok(Box::new(tls::connect(stream, tls, addr).await?)),
+        #[cfg(not(feature = "tls"))]
+        Some(_) => bail!("mcstat was built without TLS support"),
+        None => Ok(stream),
+    }
+}
+
+/// opens the connection to the server without TLS
+async fn connect_plain(
+    addr: &str,
+    port: u16,
+    options: &FetchOptions,
+) -> miette::Result<Box<dyn Stream>> {
+    match &options.unix {
+        #[cfg(unix)]
+        Some(path) => {
+            info!("Connecting to unix socket {}", path.display());
+            let stream = tokio::net::UnixStream::connect(path)
+                .await
+                .into_diagnostic()
+                .wrap_err_with(|| format!("Failed to connect to {}", path.display()))?;
+            Ok(Box::new(stream))
+        },
+        #[cfg(not(unix))]
+        Some(_) => bail!("Unix sockets are not supported on this platform"),
+        None => Ok(Box::new(connect_tcp(addr, port, options).await?)),
+    }
+}
+
+/// opens a TCP connection to the server, through the proxy if one is set
+async fn connect_tcp(addr: &str, port: u16, options: &FetchOptions) -> miette::Result<TcpStream> {
+    match &options.proxy {
+        Some(proxy) => {
+            info!("Connecting to proxy {}", proxy.addr);
+            let stream = connect_socket(&*proxy.addr, options.bind)
+                .await
+                .into_diagnostic()
+                .wrap_err("Failed to connect to proxy")?;
+            socks::connect(stream, proxy, addr, port).await
+        },
+        None => {
+            let ip = match (options.resolver, addr.parse::<IpAddr>()) {
+                (Some(nameserver), Err(_)) => {
+                    info!("Looking up {} using {}", addr, nameserver);
+                    let ip = dns_resolver(Some(nameserver))?
+                        .lookup_ip(addr)
+                        .await
+                        .into_diagnostic()
+                        .wrap_err("Failed to resolve address")?
+                        .iter()
+                        .find(|ip| {
+                            options
+                                .bind
+                                .map_or(true, |bind| bind.is_ipv4() == ip.is_ipv4())
+                        })
+                        .ok_or_else(|| miette!("Address {} could not be resolved", addr))?;
+                    Some(ip)
+                },
+                _ => None,
+            };
+
+            info!("Connecting to server");
+            let connected = match ip {
+                Some(ip) => connect_socket((ip, port), options.bind).await,
+                None => connect_socket((addr, port), options.bind).await,
+            };
+            connected
+                .into_diagnostic()
+                .wrap_err("Failed to connect to server")
+        },
+    }
+}
+
+/// opens a TCP connection to `target`, from the local address `bind` if given
+async fn connect_socket(target: impl ToSocketAddrs, bind: Option<IpAddr>) -> io::Result<TcpStream> {
+    let bind = match bind {
+        Some(bind) => bind,
+        None => return TcpStream::connect(target).await,
+    };
+
+    let mut error = None;
+    for target in lookup_host(target).await? {
+        // the target must be reachable from the bind address
+        if target.is_ipv4() != bind.is_ipv4() {
+            continue;
+        }
+
+        let socket = if bind.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            TcpSocket::new_v6()?
+        };
+        socket.bind(SocketAddr::new(bind, 0))?;
+
+        match socket.connect(target).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) => error = Some(e),
+        }
+    }
+
+    Err(error.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::AddrNotAvailable,
+            "the address has no IP of the same version as the bind address",
+        )
+    }))
+}
+
+/// creates a UDP socket connected to the given address, bound to `bind` if
+/// given
+pub(crate) async fn connect_udp(
+    addr: &str,
+    port: u16,
+    bind: Option<IpAddr>,
+) -> miette::Result<UdpSocket> {
+    let target = lookup_host((addr, port))
+        .await
+        .into_diagnostic()
+        .wrap_err("Failed to resolve address")?
+        .find(|target| bind.map_or(true, |bind| bind.is_ipv4() == target.is_ipv4()))
+        .ok_or_else(|| miette!("Address {} could not be resolved", addr))?;
+
+    let local_addr = match bind {
+        Some(bind) => SocketAddr::new(bind, 0),
+        None if target.is_ipv4() => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        None => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+    };
+
+    let socket = UdpSocket::bind(local_addr)
+        .await
+        .into_diagnostic()
+        .wrap_err("Failed to bind UDP socket")?;
+    socket
+        .connect(target)
+        .await
+        .into_diagnostic()
+        .wrap_err("Failed to connect to server")?;
+
+    Ok(socket)
+}
+
+/// the way `get_table` formats its entries
+#[derive(Debug, Clone, Copy)]
+pub enum ListFormat<'a> {
+    /// only the left strings, one per line
+    Names,
+    /// the left and right strings, aligned in two columns. If a header row is
+    /// given, it is the first line, so the result can be printed with
+    /// `HeaderedContent`.
+    Columns(Option<[&'a str; 2]>),
+    /// CSV with the given header row
+    Csv([&'a str; 2]),
+}
+
+/// formats a iterator to a readable list
 pub fn get_table<'a>(
     entries: impl Iterator<Item = (&'a str, &'a str)> + Clone,
-    second_column: bool,
+    format: ListFormat<'_>,
 ) -> String {
-    if second_column {
-        let mut table = Table::new();
-        for entry in entries {
-            table.small_entry(entry.0, entry.1.to_string());
-        }
-        let mut cursor = Cursor::new(Vec::<u8>::new());
-        table.print(&mut cursor).unwrap();
-        String::from_utf8(cursor.into_inner()).unwrap()
-    } else {
-        // this syntax is used due to a nightly function which will be added to rust
-        // also called intersperse
-        Itertools::intersperse(entries.map(|x| x.0), "\n").collect()
+    match format {
+        ListFormat::Columns(header) => {
+            let mut table = Table::new();
+            for entry in entries {
+                table.small_entry(entry.0, entry.1.to_string());
+            }
+
+            let mut out = String::new();
+            if let Some([left, right]) = header {
+                table.small_entry_width = table.small_entry_width.max(left.width());
+                out = format!(
+                    "{}{} | {}\n",
+                    left,
+                    " ".repeat(table.small_entry_width - left.width()),
+                    right
+                );
+            }
+
+            let mut cursor = Cursor::new(Vec::<u8>::new());
+            table.print(&mut cursor).unwrap();
+            out.push_str(&String::from_utf8(cursor.into_inner()).unwrap());
+            out
+        },
+        ListFormat::Names => {
+            // this syntax is used due to a nightly function which will be added to rust
+            // also called intersperse
+            Itertools::intersperse(entries.map(|x| x.0), "\n").collect()
+        },
+        ListFormat::Csv([left, right]) => {
+            let mut csv = csv_row(left, right);
+            for (left, right) in entries {
+                csv.push_str(&csv_row(left, right));
+            }
+            csv
+        },
     }
 }
 
-/// parses a base64 formatted image
-pub fn parse_base64_image(data: String) -> miette::Result<DynamicImage> {
-    info!("Parsing base64 image");
+/// formats a CSV row with two fields, quoting them if required
+fn csv_row(left: &str, right: &str) -> String {
+    fn field(s: &str) -> Cow<'_, str> {
+        if s.contains(&[',', '"', '\n', '\r'][..]) {
+            Cow::Owned(format!("\"{}\"", s.replace('"', "\"\"")))
+        } else {
+            Cow::Borrowed(s)
+        }
+    }
+
+    format!("{},{}\n", field(left), field(right))
+}
+
+/// returns the format of a base64 formatted image as given in its header, like
+/// `png`
+pub fn base64_image_format(data: &str) -> miette::Result<&str> {
+    split_base64_image(data).map(|(format, _)| format)
+}
+
+/// splits a base64 formatted image into the image format and the base64 data
+fn split_base64_image(data: &str) -> miette::Result<(&str, &str)> {
     let (header, data) = data
         .split_once(',')
         .ok_or_else(|| miette!("Couldn't parse base64 image due to missing format header."))?;
-    let (data_type, image_format) = header
-        .split_once('/')
-        .ok_or_else(|| miette!("Failed to parse base64 image, header has invalid format."))?;
-    let image_format = image_format
-        .split(';')
+    // the header may contain parameters like `charset` in any order
+    let mut params = header.split(';');
+    let (data_type, image_format) = params
         .next()
+        .and_then(|media_type| media_type.split_once('/'))
         .ok_or_else(|| miette!("Failed to parse base64 image, header has invalid format."))?;
 
-    if data_type != "data:image" {
+    if data_type.trim() != "data:image" {
         bail!("base64 image is not an image! Has type {}", data_type);
     }
 
+    if !params.any(|p| p.trim().eq_ignore_ascii_case("base64")) {
+        bail!("Image data is not base64 encoded. Header is {:?}", header);
+    }
+
+    Ok((image_format.trim(), data))
+}
+
+/// the default size limit of images decoded by `parse_base64_image`
+pub const MAX_IMAGE_BYTES: usize = 1024 * 1024;
+
+/// the largest width and height of images decoded by `parse_base64_image`
+pub const MAX_IMAGE_DIMENSION: u32 = 4096;
+
+/// parses a base64 formatted image. Images larger than `max_bytes` or
+/// `MAX_IMAGE_DIMENSION` are rejected, since decoding them could take very
+/// long.
+pub fn parse_base64_image(data: String, max_bytes: usize) -> miette::Result<DynamicImage> {
+    info!("Parsing base64 image");
+    let (image_format, data) = split_base64_image(&data)?;
+
     let format = ImageFormat::from_extension(image_format).ok_or_else(|| {
         miette!(
             "Failed to parse base64 image due to unknown image type: {}",
@@ -138,7 +959,846 @@ pub fn parse_base64_image(data: String) -> miette::Result<DynamicImage> {
     let data = base64::decode(data)
         .into_diagnostic()
         .wrap_err("Failed to decode base64 image data")?;
-    image::load(Cursor::new(data), format)
+
+    if data.len() > max_bytes {
+        bail!(
+            "Image is {} bytes large, which is more than the maximum of {} bytes",
+            data.len(),
+            max_bytes
+        );
+    }
+
+    // the dimensions are read from the header, before decoding the image
+    let (width, height) = image::io::Reader::with_format(Cursor::new(&data), format)
+        .into_dimensions()
         .into_diagnostic()
-        .wrap_err("Failed to load base64 image")
+        .wrap_err("Failed to read image dimensions")?;
+    if width > MAX_IMAGE_DIMENSION || height > MAX_IMAGE_DIMENSION {
+        bail!(
+            "Image is {}x{} pixels large, which is more than the maximum of {}x{}",
+            width,
+            height,
+            MAX_IMAGE_DIMENSION,
+            MAX_IMAGE_DIMENSION
+        );
+    }
+
+    load_image_bytes(&data, Some(format))
+}
+
+/// loads an image from its encoded bytes. If no format is given, it is guessed
+/// from the data.
+pub fn load_image_bytes(
+    data: &[u8],
+    format_hint: Option<ImageFormat>,
+) -> miette::Result<DynamicImage> {
+    match format_hint {
+        Some(format) => image::load(Cursor::new(data), format),
+        None => image::load_from_memory(data),
+    }
+    .into_diagnostic()
+    .wrap_err("Failed to load image")
+}
+
+/// returns the average color of the visible pixels of an image as RGB
+pub fn average_color(img: &DynamicImage) -> [u8; 3] {
+    // downsampling first keeps this fast, even for large images
+    let img = img.thumbnail(16, 16).to_rgba8();
+
+    let mut sum = [0u64; 3];
+    let mut weight = 0u64;
+    for pixel in img.pixels() {
+        let [r, g, b, a] = pixel.0;
+        let a = u64::from(a);
+        sum[0] += u64::from(r) * a;
+        sum[1] += u64::from(g) * a;
+        sum[2] += u64::from(b) * a;
+        weight += a;
+    }
+
+    if weight == 0 {
+        return [0; 3];
+    }
+    sum.map(|c| (c / weight) as u8)
+}
+
+/// saves an image to `path`, inferring the format from its extension. PNG is
+/// used if the extension is missing or unknown.
+pub fn save_image(img: &DynamicImage, path: &Path) -> miette::Result<()> {
+    let format = path
+        .extension()
+        .and_then(ImageFormat::from_extension)
+        .unwrap_or(ImageFormat::Png);
+
+    info!("Saving image as {:?}", format);
+    img.save_with_format(path, format)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Failed to save image to {}", path.display()))
+}
+
+/// converts alternate formatting codes to `§` codes. These are `&` followed by
+/// a valid code character, as used in config files, and the literal escape
+/// `\u00a7`. Other `&` characters are left as-is.
+pub fn amp_codes_to_section(s: &str) -> String {
+    let s = s.replace("\\u00a7", "§");
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('&', Some(code)) if "0123456789abcdefklmnorx".contains(code.to_ascii_lowercase()) => {
+                out.push('§');
+                out.push(code.to_ascii_lowercase());
+                chars.next();
+            },
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// removes all `§` formatting codes from `s`
+pub fn strip_mc_formatting(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            // skip the code character
+            chars.next();
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// returns the width of a line of text with `§` formatting codes in the
+/// terminal, as printed by `McFormatContent`. Formatting codes are skipped,
+/// except for malformed hex colors, which are printed as-is.
+pub fn mc_formatted_width(s: &str) -> usize {
+    let mut splits = s.split('§');
+    let mut width = splits.next().map(|s| s.width()).unwrap_or_default();
+
+    while let Some(split) = splits.next() {
+        let mut chars = split.chars();
+        match chars.next() {
+            Some('x') => match output::parse_hex_color(&mut splits) {
+                Some((_, rest)) => width += rest.width(),
+                None => {
+                    let digits = output::malformed_hex_digits(&mut splits);
+                    width += "§".width() + split.width() + digits.width();
+                },
+            },
+            Some(_) => width += chars.as_str().width(),
+            None => {},
+        }
+    }
+
+    width
+}
+
+/// removes all control characters except for newlines from `s`, so text sent
+/// by a server can't contain escape sequences that mess up the terminal
+pub fn strip_control_chars(s: &str) -> Cow<'_, str> {
+    let is_stripped = |c: char| c.is_control() && c != '\n';
+    if s.contains(is_stripped) {
+        Cow::Owned(s.chars().filter(|&c| !is_stripped(c)).collect())
+    } else {
+        Cow::Borrowed(s)
+    }
+}
+
+/// style state of a chat component, inherited by its children
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+struct ChatStyle<'a> {
+    color: Option<&'a str>,
+    bold: bool,
+    italic: bool,
+    underlined: bool,
+    strikethrough: bool,
+    obfuscated: bool,
+    /// whether text written in this style contained legacy `§` codes. Their
+    /// style isn't known, so it must be reset before the next component.
+    legacy_codes: bool,
+}
+
+impl<'a> ChatStyle<'a> {
+    /// returns the style of a component with the given fields that is a child
+    /// of a component with this style
+    fn inherit(self, component: &'a Map<String, Value>) -> Self {
+        let flag = |name: &str, parent: bool| {
+            component
+                .get(name)
+                .and_then(Value::as_bool)
+                .unwrap_or(parent)
+        };
+
+        Self {
+            color: match component.get("color").and_then(Value::as_str) {
+                Some("reset") => None,
+                Some(c) => Some(c),
+                None => self.color,
+            },
+            bold: flag("bold", self.bold),
+            italic: flag("italic", self.italic),
+            underlined: flag("underlined", self.underlined),
+            strikethrough: flag("strikethrough", self.strikethrough),
+            obfuscated: flag("obfuscated", self.obfuscated),
+            legacy_codes: false,
+        }
+    }
+
+    /// writes the `§` codes to apply this style to `out`
+    fn write_codes(&self, out: &mut String) {
+        if let Some(color) = self.color {
+            if let Some(hex) = color.strip_prefix('#') {
+                if hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                    out.push_str("§x");
+                    for c in hex.chars() {
+                        out.push('§');
+                        out.push(c.to_ascii_lowercase());
+                    }
+                }
+            } else if let Some(code) = color_code(color) {
+                out.push('§');
+                out.push(code);
+            }
+        }
+
+        for (enabled, code) in [
+            (self.obfuscated, 'k'),
+            (self.bold, 'l'),
+            (self.strikethrough, 'm'),
+            (self.underlined, 'n'),
+            (self.italic, 'o'),
+        ] {
+            if enabled {
+                out.push('§');
+                out.push(code);
+            }
+        }
+    }
+}
+
+/// returns the legacy formatting code of a named chat color
+fn color_code(name: &str) -> Option<char> {
+    Some(match name {
+        "black" => '0',
+        "dark_blue" => '1',
+        "dark_green" => '2',
+        "dark_aqua" => '3',
+        "dark_red" => '4',
+        "dark_purple" => '5',
+        "gold" => '6',
+        "gray" => '7',
+        "dark_gray" => '8',
+        "blue" => '9',
+        "green" => 'a',
+        "aqua" => 'b',
+        "red" => 'c',
+        "light_purple" => 'd',
+        "yellow" => 'e',
+        "white" => 'f',
+        _ => return None,
+    })
+}
+
+/// converts a JSON chat component to an equivalent string with `§` formatting
+/// codes, which can be displayed using `McFormatContent`
+pub fn chat_component_to_mc_formatted(component: &Value) -> String {
+    let mut out = String::new();
+    write_chat_component(
+        component,
+        ChatStyle::default(),
+        &mut ChatStyle::default(),
+        &mut out,
+    );
+    out
+}
+
+fn write_chat_component<'a>(
+    component: &'a Value,
+    parent: ChatStyle<'a>,
+    current: &mut ChatStyle<'a>,
+    out: &mut String,
+) {
+    match component {
+        Value::Object(obj) => {
+            let style = parent.inherit(obj);
+            if let Some(text) = obj.get("text").and_then(Value::as_str) {
+                write_styled_text(text, style, current, out);
+            }
+
+            if let Some(key) = obj.get("translate").and_then(Value::as_str) {
+                let args = match obj.get("with") {
+                    Some(Value::Array(args)) => &args[..],
+                    _ => &[],
+                };
+                write_translation(key, args, style, current, out);
+            }
+
+            if let Some(Value::Array(extra)) = obj.get("extra") {
+                for child in extra {
+                    write_chat_component(child, style, current, out);
+                }
+            }
+        },
+        Value::Array(parts) => {
+            // the first element of an array is the parent of all the following ones
+            if let Some((first, rest)) = parts.split_first() {
+                write_chat_component(first, parent, current, out);
+
+                let style = match first {
+                    Value::Object(obj) => parent.inherit(obj),
+                    _ => parent,
+                };
+                for child in rest {
+                    write_chat_component(child, style, current, out);
+                }
+            }
+        },
+        Value::String(text) => write_styled_text(text, parent, current, out),
+        Value::Number(n) => write_styled_text(&n.to_string(), parent, current, out),
+        Value::Bool(b) => write_styled_text(&b.to_string(), parent, current, out),
+        Value::Null => {},
+    }
+}
+
+/// writes the translation of `key` with the `%s` placeholders replaced by
+/// `args`. Keys without a known translation are written in brackets.
+fn write_translation<'a>(
+    key: &str,
+    args: &'a [Value],
+    style: ChatStyle<'a>,
+    current: &mut ChatStyle<'a>,
+    out: &mut String,
+) {
+    match TRANSLATIONS.iter().find(|(k, _)| *k == key) {
+        Some((_, template)) => write_template(template, args, style, current, out),
+        None => write_styled_text(&format!("[{}]", key), style, current, out),
+    }
+}
+
+/// writes `template` with its `%s` and `%1$s` placeholders replaced by `args`
+fn write_template<'a>(
+    template: &str,
+    args: &'a [Value],
+    style: ChatStyle<'a>,
+    current: &mut ChatStyle<'a>,
+    out: &mut String,
+) {
+    let mut next_arg = 0;
+    let mut rest = template;
+    while let Some(idx) = rest.find('%') {
+        write_styled_text(&rest[..idx], style, current, out);
+        rest = &rest[idx + 1..];
+
+        // placeholders are either `%s` or positional like `%1$s`
+        let arg = if let Some(r) = rest.strip_prefix('s') {
+            rest = r;
+            next_arg += 1;
+            next_arg - 1
+        } else if let Some((n, r)) = rest
+            .split_once("$s")
+            .and_then(|(n, r)| Some((n.parse::<usize>().ok()?, r)))
+        {
+            rest = r;
+            n.saturating_sub(1)
+        } else {
+            // `%%` and invalid placeholders
+            write_styled_text("%", style, current, out);
+            rest = rest.strip_prefix('%').unwrap_or(rest);
+            continue;
+        };
+
+        if let Some(arg) = args.get(arg) {
+            write_chat_component(arg, style, current, out);
+        }
+    }
+    write_styled_text(rest, style, current, out);
+}
+
+/// english translations of keys servers commonly use in their status
+const TRANSLATIONS: &[(&str, &str)] = &[
+    ("multiplayer.status.and_more", "... and %s more ..."),
+    (
+        "multiplayer.status.cannot_connect",
+        "Can't connect to server",
+    ),
+    (
+        "multiplayer.status.cannot_resolve",
+        "Can't resolve hostname",
+    ),
+    ("multiplayer.status.finished", "Finished"),
+    ("multiplayer.status.incompatible", "Incompatible version!"),
+    ("multiplayer.status.no_connection", "(no connection)"),
+    ("multiplayer.status.old", "Old"),
+    ("multiplayer.status.pinging", "Pinging..."),
+    ("multiplayer.status.quitting", "Quitting"),
+    (
+        "multiplayer.status.request_handled",
+        "Status request has been handled",
+    ),
+    ("multiplayer.status.unknown", "???"),
+    (
+        "multiplayer.status.unrequested",
+        "Received unrequested status",
+    ),
+    ("chat.type.text", "<%s> %s"),
+    ("chat.type.announcement", "[%s] %s"),
+];
+
+/// writes `text` to `out`, preceded by the codes required to switch from the
+/// `current` style to `style`
+fn write_styled_text<'a>(
+    text: &str,
+    style: ChatStyle<'a>,
+    current: &mut ChatStyle<'a>,
+    out: &mut String,
+) {
+    if text.is_empty() {
+        return;
+    }
+
+    if style != *current {
+        if *current != ChatStyle::default() {
+            out.push_str("§r");
+        }
+        style.write_codes(out);
+        *current = style;
+    }
+
+    out.push_str(text);
+    if text.contains('§') {
+        current.legacy_codes = true;
+    }
+}
+
+/// returns the hex color of a legacy formatting color code
+fn color_hex(code: char) -> Option<&'static str> {
+    Some(match code {
+        '0' => "#000000",
+        '1' => "#0000aa",
+        '2' => "#00aa00",
+        '3' => "#00aaaa",
+        '4' => "#aa0000",
+        '5' => "#aa00aa",
+        '6' => "#ffaa00",
+        '7' => "#aaaaaa",
+        '8' => "#555555",
+        '9' => "#5555ff",
+        'a' => "#55ff55",
+        'b' => "#55ffff",
+        'c' => "#ff5555",
+        'd' => "#ff55ff",
+        'e' => "#ffff55",
+        'f' => "#ffffff",
+        _ => return None,
+    })
+}
+
+/// escapes the characters of `s` that have a special meaning in HTML
+pub fn html_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// style of `§` formatted text while converting it to HTML
+#[derive(Clone, Default, PartialEq, Eq)]
+struct HtmlStyle {
+    /// the color as `#rrggbb`
+    color: Option<String>,
+    bold: bool,
+    italic: bool,
+    underlined: bool,
+    strikethrough: bool,
+}
+
+impl HtmlStyle {
+    fn tags(&self) -> [(bool, &'static str); 4] {
+        [
+            (self.bold, "b"),
+            (self.italic, "i"),
+            (self.underlined, "u"),
+            (self.strikethrough, "s"),
+        ]
+    }
+
+    fn open(&self, out: &mut String) {
+        if let Some(color) = &self.color {
+            out.push_str(&format!("<span style=\"color:{}\">", color));
+        }
+
+        for (_, tag) in self.tags().into_iter().filter(|(enabled, _)| *enabled) {
+            out.push_str(&format!("<{}>", tag));
+        }
+    }
+
+    fn close(&self, out: &mut String) {
+        for (_, tag) in self
+            .tags()
+            .into_iter()
+            .rev()
+            .filter(|(enabled, _)| *enabled)
+        {
+            out.push_str(&format!("</{}>", tag));
+        }
+
+        if self.color.is_some() {
+            out.push_str("</span>");
+        }
+    }
+}
+
+/// converts a string with `§` formatting codes to HTML. Colors are converted
+/// to `<span>`s and formatting to `<b>`, `<i>`, `<u>` and `<s>` tags. The text
+/// is escaped, so the output is safe to embed into a page.
+pub fn mc_formatted_to_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut style = HtmlStyle::default();
+    // the style of the tags that are currently open
+    let mut open = HtmlStyle::default();
+
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '§' {
+            if style != open {
+                open.close(&mut out);
+                style.open(&mut out);
+                open = style.clone();
+            }
+
+            out.push_str(&html_escape(c.encode_utf8(&mut [0; 4])));
+            continue;
+        }
+
+        match chars.next().map(|c| c.to_ascii_lowercase()) {
+            // hex colors, sent as §x§R§R§G§G§B§B
+            Some('x') => {
+                let mut lookahead = chars.clone();
+                let hex = (0..6)
+                    .map(|_| match (lookahead.next(), lookahead.next()) {
+                        (Some('§'), Some(d)) if d.is_ascii_hexdigit() => Some(d),
+                        _ => None,
+                    })
+                    .collect::<Option<String>>();
+
+                if let Some(hex) = hex {
+                    chars = lookahead;
+                    style = HtmlStyle {
+                        color: Some(format!("#{}", hex.to_ascii_lowercase())),
+                        ..Default::default()
+                    };
+                }
+            },
+            // a color resets the formatting
+            Some(code) if color_hex(code).is_some() => {
+                style = HtmlStyle {
+                    color: color_hex(code).map(String::from),
+                    ..Default::default()
+                };
+            },
+            Some('l') => style.bold = true,
+            Some('m') => style.strikethrough = true,
+            Some('n') => style.underlined = true,
+            Some('o') => style.italic = true,
+            Some('r') => style = HtmlStyle::default(),
+            _ => {},
+        }
+    }
+
+    open.close(&mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_port_bracketed_ipv6_with_port() {
+        let (addr, port) = split_port("[2001:db8::1]:25565").unwrap();
+        assert_eq!(addr, "2001:db8::1");
+        assert_eq!(port, Some(25565));
+    }
+
+    #[test]
+    fn split_port_bracketed_ipv6() {
+        let (addr, port) = split_port("[2001:db8::1]").unwrap();
+        assert_eq!(addr, "2001:db8::1");
+        assert_eq!(port, None);
+    }
+
+    #[test]
+    fn split_port_bare_ipv6() {
+        let (addr, port) = split_port("2001:db8::1").unwrap();
+        assert_eq!(addr, "2001:db8::1");
+        assert_eq!(port, None);
+    }
+
+    #[test]
+    fn split_port_missing_bracket() {
+        assert!(split_port("[2001:db8::1:25565").is_err());
+    }
+
+    #[test]
+    fn mc_formatted_width_plain() {
+        assert_eq!(mc_formatted_width("A Minecraft Server"), 18);
+    }
+
+    #[test]
+    fn mc_formatted_width_codes() {
+        assert_eq!(mc_formatted_width("§aGreen §lbold§r text"), 15);
+    }
+
+    #[test]
+    fn mc_formatted_width_hex_color() {
+        assert_eq!(mc_formatted_width("§x§f§f§8§8§0§0Orange"), 6);
+    }
+
+    #[test]
+    fn mc_formatted_width_trailing_section_sign() {
+        assert_eq!(mc_formatted_width("Server§"), 6);
+    }
+
+    #[test]
+    fn mc_formatted_width_cjk() {
+        // wide characters take up two columns each
+        assert_eq!(mc_formatted_width("§6我的世界§r服务器"), 14);
+    }
+
+    /// a status response with the given JSON description
+    fn status_with_description(description: &str) -> String {
+        format!(
+            r#"{{
+                "version": {{ "name": "1.19.4", "protocol": 762 }},
+                "players": {{ "online": 1, "max": 20 }},
+                "description": {}
+            }}"#,
+            description
+        )
+    }
+
+    /// the description of a parsed status, failing if it isn't a status
+    fn parsed_description(raw: &str) -> String {
+        match parse_status(raw).unwrap().1 {
+            EitherStatusResponse::Normal(status) => status.description.get_text().to_string(),
+            EitherStatusResponse::Text { text } => panic!("expected a status, got {:?}", text),
+        }
+    }
+
+    #[test]
+    fn parse_status_string_description() {
+        let raw = status_with_description(r#""Hello §aWorld""#);
+        assert_eq!(parsed_description(&raw), "Hello §aWorld");
+
+        // the raw JSON is kept as it was sent
+        let (json, _) = parse_status(&raw).unwrap();
+        assert_eq!(json["description"], "Hello §aWorld");
+    }
+
+    #[test]
+    fn parse_status_array_description() {
+        let raw = status_with_description(r#"["Hello ", { "text": "World", "color": "green" }]"#);
+        assert_eq!(parsed_description(&raw), "Hello §aWorld");
+    }
+
+    #[test]
+    fn split_base64_image_with_charset() {
+        let (format, data) =
+            split_base64_image("data:image/png;charset=utf-8;base64,iVBORw0K").unwrap();
+        assert_eq!(format, "png");
+        assert_eq!(data, "iVBORw0K");
+    }
+
+    #[test]
+    fn split_base64_image_reordered_params() {
+        let (format, _) =
+            split_base64_image("data:image/png;base64;charset=utf-8,iVBORw0K").unwrap();
+        assert_eq!(format, "png");
+    }
+
+    #[test]
+    fn split_base64_image_not_base64() {
+        assert!(split_base64_image("data:image/png;charset=utf-8,iVBORw0K").is_err());
+    }
+
+    #[test]
+    fn split_base64_image_not_an_image() {
+        assert!(split_base64_image("data:text/plain;base64,aGVsbG8=").is_err());
+    }
+
+    /// `template` with its placeholders replaced by `args`
+    fn formatted_template(template: &str, args: &[Value]) -> String {
+        let mut out = String::new();
+        write_template(
+            template,
+            args,
+            ChatStyle::default(),
+            &mut ChatStyle::default(),
+            &mut out,
+        );
+        out
+    }
+
+    #[test]
+    fn translation_mapped_key() {
+        let component = json!({
+            "translate": "chat.type.text",
+            "with": ["Notch", "hello"],
+        });
+        assert_eq!(chat_component_to_mc_formatted(&component), "<Notch> hello");
+    }
+
+    #[test]
+    fn translation_unmapped_key() {
+        let component = json!({ "translate": "some.unknown.key", "with": ["a"] });
+        assert_eq!(
+            chat_component_to_mc_formatted(&component),
+            "[some.unknown.key]"
+        );
+    }
+
+    #[test]
+    fn translation_placeholders() {
+        let args = [json!("a"), json!("b")];
+        assert_eq!(formatted_template("%s and %s", &args), "a and b");
+        assert_eq!(formatted_template("%2$s before %1$s", &args), "b before a");
+        assert_eq!(formatted_template("100%% of %s", &args), "100% of a");
+        assert_eq!(formatted_template("%s %s %s", &args), "a b ");
+    }
+
+    #[test]
+    fn strip_scheme_minecraft() {
+        assert_eq!(
+            strip_scheme("minecraft://mc.example.com").unwrap(),
+            "mc.example.com"
+        );
+    }
+
+    #[test]
+    fn strip_scheme_tcp_with_port() {
+        assert_eq!(
+            strip_scheme("tcp://mc.example.com:25566").unwrap(),
+            "mc.example.com:25566"
+        );
+        assert_eq!(
+            split_port("TCP://mc.example.com:25566/").unwrap(),
+            ("mc.example.com", Some(25566))
+        );
+    }
+
+    #[test]
+    fn strip_scheme_trailing_slash() {
+        assert_eq!(strip_scheme("mc.example.com/").unwrap(), "mc.example.com");
+        assert_eq!(
+            strip_scheme("minecraft://mc.example.com//").unwrap(),
+            "mc.example.com"
+        );
+    }
+
+    #[test]
+    fn strip_scheme_unsupported() {
+        assert!(strip_scheme("https://mc.example.com").is_err());
+    }
+
+    #[test]
+    fn chat_component_inherits_through_extra() {
+        let component = json!({
+            "text": "a",
+            "color": "red",
+            "extra": [{ "text": "b", "bold": true }, { "text": "c" }],
+        });
+        assert_eq!(
+            chat_component_to_mc_formatted(&component),
+            "§ca§r§c§lb§r§cc"
+        );
+    }
+
+    #[test]
+    fn chat_component_resets_between_siblings() {
+        let component = json!(["", { "text": "a", "color": "red" }, { "text": "b" }]);
+        assert_eq!(chat_component_to_mc_formatted(&component), "§ca§rb");
+    }
+
+    #[test]
+    fn chat_component_resets_embedded_legacy_codes() {
+        let component = json!(["", { "text": "§aGreen" }, { "text": " plain" }]);
+        assert_eq!(
+            chat_component_to_mc_formatted(&component),
+            "§aGreen§r plain"
+        );
+
+        // the style of the component is applied again after the reset
+        let component = json!({
+            "text": "",
+            "color": "red",
+            "extra": ["§lbold", " red"],
+        });
+        assert_eq!(
+            chat_component_to_mc_formatted(&component),
+            "§c§lbold§r§c red"
+        );
+    }
+
+    #[test]
+    fn protocol_version_range() {
+        assert_eq!("auto".parse(), Ok(ProtocolVersion::Auto));
+        assert_eq!("-1".parse(), Ok(ProtocolVersion::Auto));
+        assert_eq!("762".parse(), Ok(ProtocolVersion::Number(762)));
+        assert_eq!("2147483647".parse(), Ok(ProtocolVersion::Number(i32::MAX)));
+        assert!("4294967295".parse::<ProtocolVersion>().is_err());
+        assert!("-5".parse::<ProtocolVersion>().is_err());
+    }
+
+    #[test]
+    fn csv_row_plain() {
+        assert_eq!(csv_row("jei", "11.6.0"), "jei,11.6.0\n");
+    }
+
+    #[test]
+    fn csv_row_quoting() {
+        assert_eq!(csv_row("a,b", "1.0"), "\"a,b\",1.0\n");
+        assert_eq!(csv_row("say \"hi\"", "1.0"), "\"say \"\"hi\"\"\",1.0\n");
+        assert_eq!(csv_row("mod", "line\nbreak"), "mod,\"line\nbreak\"\n");
+        assert_eq!(csv_row("mod", "cr\r"), "mod,\"cr\r\"\n");
+    }
+
+    #[test]
+    fn mc_formatted_to_html_escapes() {
+        assert_eq!(
+            mc_formatted_to_html("<b>Tom & \"Jerry\"</b>"),
+            "&lt;b&gt;Tom &amp; &quot;Jerry&quot;&lt;/b&gt;"
+        );
+    }
+
+    #[test]
+    fn mc_formatted_to_html_closes_unclosed_styles() {
+        assert_eq!(
+            mc_formatted_to_html("§cRed §lbold"),
+            "<span style=\"color:#ff5555\">Red </span><span \
+             style=\"color:#ff5555\"><b>bold</b></span>"
+        );
+        assert_eq!(
+            mc_formatted_to_html("§nunder§r plain"),
+            "<u>under</u> plain"
+        );
+    }
+
+    #[test]
+    fn mc_formatted_to_html_hex_color() {
+        assert_eq!(
+            mc_formatted_to_html("§x§F§F§8§8§0§0<Orange>"),
+            "<span style=\"color:#ff8800\">&lt;Orange&gt;</span>"
+        );
+    }
 }
@@ -1,41 +1,435 @@
-use async_minecraft_ping::{ConnectionConfig, ServerDescription, StatusResponse};
+mod config;
 
-use clap::Parser;
+use async_minecraft_ping::StatusResponse;
+
+use clap::{
+    Arg,
+    ArgEnum,
+    ArgMatches,
+    Args,
+    CommandFactory,
+    ErrorKind,
+    FromArgMatches,
+    Parser,
+    Subcommand,
+    ValueSource,
+};
+use config::Config;
+use crossterm::{
+    cursor::MoveTo,
+    event::{self, Event, KeyCode, KeyModifiers},
+    style::{Attribute, Color, ResetColor, SetAttribute},
+    terminal::{self, Clear, ClearType},
+    tty::IsTty,
+    ExecutableCommand,
+};
+use futures::future::join_all;
+use image::DynamicImage;
 use itertools::Itertools;
-use miette::{IntoDiagnostic, WrapErr};
-use time::Duration;
-use tokio::time;
+use miette::{bail, miette, IntoDiagnostic, WrapErr};
+use std::{
+    collections::VecDeque,
+    env,
+    fmt,
+    fs::{self, File},
+    io::{self, Write},
+    net::IpAddr,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{signal, sync::Semaphore, time};
 
 use mcstat::{
+    amp_codes_to_section,
+    average_color,
+    base64_image_format,
+    chat_component_to_mc_formatted,
+    discovery,
+    fetch_raw_status,
     get_table,
     none_if_empty,
-    output::{McFormatContent, Table},
+    output::{
+        sparkline,
+        ColoredValue,
+        CompactTable,
+        DeltaValue,
+        HeaderedContent,
+        McFormatContent,
+        Table,
+    },
     parse_base64_image,
-    resolve_address,
+    parse_status,
+    ping_stats,
+    protocol_versions,
+    query::{self, FullStat},
+    save_image,
+    socks::Proxy,
+    strip_control_chars,
+    strip_mc_formatting,
     EitherStatusResponse,
+    FetchOptions,
+    ListFormat,
+    ProtocolVersion,
+    RawStatus,
+    ResolveMethod,
+    ResolvedAddress,
+    StatusError,
+    TimeoutError,
+    Timing,
+    TlsOptions,
+    MAX_IMAGE_BYTES,
 };
+use serde::Serialize;
+use serde_json::Value;
 use tracing::{info, Level};
 
+/// exit code used if the connection to a server timed out
+const EXIT_TIMEOUT: u8 = 2;
+/// exit code used if a server responded with a text message instead of its
+/// status, which is usually the case during maintenance
+const EXIT_TEXT_RESPONSE: u8 = 3;
+/// exit code used if mcstat was interrupted with Ctrl-C
+const EXIT_INTERRUPTED: u8 = 130;
+
+/// the number of pings shown in the sparkline of watch mode
+const PING_HISTORY_LEN: usize = 30;
+
+/// the fields of a status response that are parsed into a `StatusResponse`
+const PARSED_FIELDS: &[&str] = &[
+    "version",
+    "players",
+    "description",
+    "favicon",
+    "modinfo",
+    "forgeData",
+];
+
+/// identifiers of the fields of the table, used by `--fields`
+const FIELDS: &[&str] = &[
+    "description",
+    "sample",
+    "players",
+    "plugins",
+    "resolved",
+    "version",
+    "software",
+    "gametype",
+    "map",
+    "online",
+    "max",
+    "ping",
+    "protocol",
+    "chat",
+    "modded",
+    "modcount",
+    "favicon",
+    "mods",
+    "channels",
+];
+
 /// Queries information about a minecraft server
+///
+/// Defaults for the options can be set in the `[defaults]` table of
+/// `$XDG_CONFIG_HOME/mcstat/config.toml`, or the file given by the
+/// `MCSTAT_CONFIG` environment variable. Servers can be given names in the
+/// `[servers]` table, which can then be used instead of their address.
 #[derive(Debug, Parser)]
-#[clap(name = "mcstat")]
+#[clap(
+    name = "mcstat",
+    args_override_self = true,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    #[clap(flatten)]
+    opt: Opt,
+}
+
+/// the subcommands of mcstat. These are shortcuts for the flags of the
+/// options, which still work without a subcommand.
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// show the status of servers. This is the default if no subcommand is
+    /// given
+    #[clap(args_override_self = true)]
+    Status(Opt),
+
+    /// show the status of servers, including the player list and plugins of
+    /// the query protocol. Same as `--query`
+    #[clap(args_override_self = true)]
+    Query(Opt),
+
+    /// list the worlds opened to LAN in the local network. Same as
+    /// `--discover`
+    #[clap(args_override_self = true)]
+    Discover(Opt),
+
+    /// show the status of servers repeatedly until interrupted. Press `q` to
+    /// quit, `r` to refresh and `+` or `-` to change the interval. Same as
+    /// `--watch`
+    #[clap(args_override_self = true)]
+    Watch {
+        /// the number of seconds between queries
+        #[clap(long, short = 'n', value_name = "SECONDS", default_value = "5")]
+        interval: u64,
+
+        #[clap(flatten)]
+        opt: Opt,
+    },
+}
+
+impl Command {
+    /// converts the subcommand to the equivalent options
+    fn into_opt(self) -> Opt {
+        match self {
+            Command::Status(opt) => opt,
+            Command::Query(opt) => Opt { query: true, ..opt },
+            Command::Discover(opt) => Opt {
+                discover: true,
+                ..opt
+            },
+            Command::Watch { interval, opt } => Opt {
+                watch: Some(interval),
+                ..opt
+            },
+        }
+    }
+}
+
+/// exits with an error if an argument conflicting with the flag a subcommand
+/// stands for is given. The flag is only set after parsing, so clap can't check
+/// this itself.
+fn check_subcommand_conflicts(matches: &ArgMatches) {
+    let (name, sub_matches) = match matches.subcommand() {
+        Some((name @ ("query" | "discover" | "watch"), sub_matches)) => (name, sub_matches),
+        _ => return,
+    };
+
+    let cli = Cli::command();
+    let sub = match cli.find_subcommand(name) {
+        Some(sub) => sub,
+        None => return,
+    };
+    let flag = match sub.get_arguments().find(|arg| arg.get_id() == name) {
+        Some(flag) => flag,
+        None => return,
+    };
+
+    let conflicts = |a: &Arg, b: &Arg| {
+        sub.get_arg_conflicts_with(a)
+            .iter()
+            .any(|c| c.get_id() == b.get_id())
+    };
+    let given = sub
+        .get_arguments()
+        .filter(|arg| arg.get_id() != name)
+        .filter(|arg| sub_matches.value_source(arg.get_id()) == Some(ValueSource::CommandLine))
+        .find(|&arg| conflicts(arg, flag) || conflicts(flag, arg));
+
+    if let Some(arg) = given {
+        let arg_name = arg
+            .get_long()
+            .map_or_else(|| arg.get_id().to_string(), |long| format!("--{}", long));
+        Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                format!(
+                    "The argument '{}' cannot be used with the '{}' subcommand",
+                    arg_name, name
+                ),
+            )
+            .exit();
+    }
+}
+
+#[derive(Debug, Args)]
 struct Opt {
-    /// The Address to ping. By default, a SRV lookup will be made to resolve
-    /// this, unless the port is specified
-    ip: String,
+    /// The Addresses to ping. By default, a SRV lookup will be made to resolve
+    /// these, unless the port is specified
+    ip: Vec<String>,
+
+    /// read the addresses to ping from PATH, one per line. Blank lines and
+    /// comments starting with `#` are ignored
+    #[clap(long, value_name = "PATH", parse(from_os_str))]
+    file: Option<PathBuf>,
+
+    /// list the worlds opened to LAN in the local network. Announcements are
+    /// received until the timeout passes
+    #[clap(long)]
+    discover: bool,
+
+    /// also query the status of each world found by --discover
+    #[clap(long)]
+    discover_query: bool,
+
+    /// the protocol version to use. `auto` sends -1, which servers answer
+    /// regardless of their version. This is the safest choice if only the
+    /// status is needed
+    #[clap(
+        long = "protocol",
+        value_name = "VERSION",
+        default_value = "751",
+        allow_hyphen_values = true
+    )]
+    protocol_version: ProtocolVersion,
+
+    /// the time before the server ping times out. Accepts units like `5s`,
+    /// `500ms` or `1m`. Bare numbers are milliseconds
+    #[clap(long, short, default_value = "5000", parse(try_from_str = parse_duration))]
+    timeout: Duration,
+
+    /// the time before resolving the address times out, in the same format as
+    /// --timeout. Defaults to the value of --timeout
+    #[clap(long, parse(try_from_str = parse_duration))]
+    resolve_timeout: Option<Duration>,
+
+    /// the number of times to measure the ping. If greater than 1, the minimum,
+    /// average and maximum are shown
+    #[clap(long, short, default_value = "1")]
+    count: usize,
+
+    /// show how long connecting and requesting the status took, in addition
+    /// to the ping
+    #[clap(long)]
+    timing_detail: bool,
+
+    /// only print the ping in milliseconds, without parsing the status. With
+    /// `--count`, the average is printed
+    #[clap(long, conflicts_with_all = &["raw", "json", "check", "compact", "query"])]
+    ping_only: bool,
+
+    /// print the status as parsed by mcstat, followed by the fields of the
+    /// response it doesn't parse. Useful for debugging
+    #[clap(
+        long,
+        conflicts_with_all = &["raw", "json", "check", "compact", "ping_only"]
+    )]
+    dump: bool,
+
+    /// only print the names of the players in the sample, one per line. With
+    /// `--query`, all players are printed
+    #[clap(
+        long,
+        conflicts_with_all = &["raw", "json", "check", "compact", "ping_only", "dump"]
+    )]
+    players_only: bool,
+
+    /// only print `up` or `down` for each server. The exit code is set as
+    /// usual if a server is down
+    #[clap(
+        long,
+        conflicts_with_all = &["raw", "json", "check", "compact", "ping_only", "dump", "players_only"]
+    )]
+    status_word: bool,
+
+    /// retry up to N times if the server can't be reached, waiting 200 ms
+    /// before the first retry and twice as long before each following one
+    #[clap(long, value_name = "N", default_value = "0")]
+    retries: usize,
+
+    /// query at most N servers at the same time
+    #[clap(long, value_name = "N", default_value = "16")]
+    concurrency: usize,
+
+    /// use the server list ping of minecraft versions before 1.7. This is also
+    /// tried if the normal status request fails
+    #[clap(long)]
+    legacy: bool,
+
+    /// ping a bedrock edition server. The port defaults to 19132 and no SRV
+    /// lookup is made
+    #[clap(long, conflicts_with = "legacy")]
+    bedrock: bool,
+
+    /// show the minecraft version belonging to the protocol version of the
+    /// server
+    #[clap(long)]
+    decode_protocol: bool,
+
+    /// connect to java edition servers through the SOCKS5 proxy at HOST:PORT
+    #[clap(long, value_name = "HOST:PORT", conflicts_with_all = &["bedrock", "query"])]
+    proxy: Option<String>,
+
+    /// the username and password to authenticate to the proxy with
+    #[clap(
+        long,
+        value_name = "USER:PASSWORD",
+        requires = "proxy",
+        parse(try_from_str = parse_proxy_auth)
+    )]
+    proxy_auth: Option<(String, String)>,
+
+    /// connect to servers from the local address ADDR
+    #[clap(long, value_name = "ADDR")]
+    bind: Option<IpAddr>,
+
+    /// don't look up the SRV record of the address, connecting to port 25565
+    /// of the host if no port is given
+    #[clap(long, conflicts_with = "bedrock")]
+    no_srv: bool,
+
+    /// connect to the unix socket at PATH instead of the server. The address
+    /// is only sent to it in the handshake. Only supported on unix
+    #[clap(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        conflicts_with_all = &["proxy", "bind", "bedrock", "query"]
+    )]
+    unix: Option<PathBuf>,
+
+    /// send DNS queries to the server at IP instead of the system's resolver
+    #[clap(long, value_name = "IP", conflicts_with = "unix")]
+    resolver: Option<IpAddr>,
+
+    /// connect to the server over TLS, for servers behind a proxy that
+    /// terminates it. Requires mcstat to be built with the `tls` feature
+    #[clap(long, conflicts_with = "bedrock")]
+    tls: bool,
+
+    /// the server name to send and verify when connecting over TLS. Defaults
+    /// to the address of the server
+    #[clap(long, value_name = "NAME", requires = "tls")]
+    tls_sni: Option<String>,
 
-    /// the protocol version to use
-    #[clap(long = "protocol", default_value = "751")]
-    protocol_version: usize,
+    /// don't verify the certificate of the server when connecting over TLS
+    #[clap(long, requires = "tls")]
+    insecure: bool,
 
-    /// the time before the server ping times out in milliseconds
-    #[clap(long, short, default_value = "5000")]
-    timeout: u64,
+    /// only show the given comma-separated fields in the table
+    #[clap(
+        long,
+        value_name = "FIELDS",
+        value_delimiter = ',',
+        possible_values = FIELDS
+    )]
+    fields: Option<Vec<String>>,
 
     /// print raw json response
     #[clap(long, short)]
     raw: bool,
 
+    /// print the raw json response indented, keeping the order of its fields
+    #[clap(long, conflicts_with = "raw")]
+    raw_pretty: bool,
+
+    /// print the status as a JSON object with a stable schema. Shorthand for
+    /// `--format json`
+    #[clap(long, conflicts_with = "raw")]
+    json: bool,
+
+    /// the output format. `auto` uses `human` if stdout is a terminal and
+    /// `json` otherwise. `csv` prints the mods and forge channels, so it
+    /// requires `--mods` or `--channels`. `markdown` prints the table as a
+    /// GitHub-flavored markdown table and `html` as a HTML table. `prometheus`
+    /// prints metrics in the Prometheus text format
+    #[clap(long, arg_enum, default_value = "auto")]
+    format: Format,
+
     /// print mod list
     #[clap(long, short)]
     mods: bool,
@@ -44,196 +438,1978 @@ struct Opt {
     #[clap(long, short = 'V', requires = "mods")]
     modversions: bool,
 
+    /// only print mods whose ID or version contains PATTERN, ignoring case
+    #[clap(long, value_name = "PATTERN", requires = "mods")]
+    mod_filter: Option<String>,
+
+    /// treat `&` followed by a formatting code character and the literal
+    /// escape `\u00a7` as `§` in the description and player sample
+    #[clap(long)]
+    amp_codes: bool,
+
+    /// sort the player sample by name
+    #[clap(long)]
+    sort_players: bool,
+
+    /// remove duplicate names from the player sample
+    #[clap(long)]
+    unique_players: bool,
+
+    /// the maximum number of players shown from the player sample. 0 shows all
+    /// of them
+    #[clap(long, value_name = "N", default_value = "20")]
+    max_players: usize,
+
+    /// how the names of the player sample are shown. `plain` removes
+    /// formatting codes and `uuid` shows the UUID of each player
+    #[clap(long, arg_enum, default_value = "formatted")]
+    sample_format: SampleFormat,
+
     /// displays forge mod channels if the server sends them
     #[clap(long)]
     channels: bool,
 
+    /// also use the query protocol to get the full player list and plugins.
+    /// This requires `enable-query` to be set on the server
+    #[clap(long, conflicts_with = "bedrock")]
+    query: bool,
+
+    /// query the server every SECONDS seconds until interrupted
+    #[clap(long, value_name = "SECONDS", conflicts_with_all = &["raw", "json"])]
+    watch: Option<u64>,
+
     /// print the server's favicon to stdout
     #[clap(long, short)]
     image: bool,
 
+    /// show the average color of the server's favicon, as a lightweight
+    /// alternative to printing it
+    #[clap(long)]
+    icon_color: bool,
+
     /// size of the favicon ascii art
     #[clap(short, requires = "image")]
     size: Option<u32>,
 
+    /// the largest favicon in bytes that is decoded. Larger ones are ignored,
+    /// since decoding them could take very long
+    #[clap(long, value_name = "BYTES", default_value_t = MAX_IMAGE_BYTES)]
+    max_icon_bytes: usize,
+
+    /// the way the favicon is printed. `auto` uses the kitty or iTerm graphics
+    /// protocols if the terminal supports them, and colored blocks otherwise
+    #[clap(long, arg_enum, value_name = "PROTOCOL", default_value = "auto")]
+    image_protocol: ImageProtocol,
+
+    /// save the server's favicon to PATH. The format is inferred from the file
+    /// extension and defaults to PNG
+    #[clap(long, value_name = "PATH", parse(from_os_str))]
+    save_icon: Option<PathBuf>,
+
+    /// only print the favicon as the data URI sent by the server
+    #[clap(
+        long,
+        conflicts_with_all = &["raw", "json", "check", "compact", "ping_only", "dump", "players_only"]
+    )]
+    icon_data: bool,
+
+    /// word-wrap long descriptions and lists to WIDTH columns. Without a
+    /// value, the width of the terminal is used
+    #[clap(long, value_name = "WIDTH")]
+    width: Option<Option<usize>>,
+
+    /// highlight the names of the table entries
+    #[clap(long)]
+    color_keys: bool,
+
+    /// draw lines between the entries of the table
+    #[clap(long)]
+    dividers: bool,
+
+    /// cut off values of single-line entries at N columns. 0 doesn't cut them
+    /// off
+    #[clap(long, value_name = "N")]
+    truncate: Option<usize>,
+
+    /// the character the borders of the description and lists are drawn with
+    #[clap(long, value_name = "CHAR", default_value = "=")]
+    border: char,
+
+    /// when to print colors and formatting. `auto` disables them if stdout is
+    /// not a terminal or the `NO_COLOR` environment variable is set
+    #[clap(long, arg_enum, value_name = "WHEN")]
+    color: Option<ColorChoice>,
+
+    /// print output without colors or formatting. Same as `--color never`
+    #[clap(long, conflicts_with_all = &["color", "force_color"])]
+    no_color: bool,
+
+    /// print colors and formatting even if the output is not a terminal. Same
+    /// as `--color always`
+    #[clap(long, conflicts_with = "color")]
+    force_color: bool,
+
+    /// write the table to PATH instead of stdout. Colors are only used with
+    /// `--color always`
+    #[clap(
+        long,
+        short,
+        value_name = "PATH",
+        parse(from_os_str),
+        conflicts_with = "watch"
+    )]
+    output: Option<PathBuf>,
+
+    /// print a single aligned line per server with the address, the player
+    /// count, the ping and the version
+    #[clap(long, conflicts_with_all = &["raw", "json", "watch", "check"])]
+    compact: bool,
+
+    /// only print a single line per server saying whether it is up. The exit
+    /// code is 0 if all servers are up, 2 if a connection timed out and 3 if a
+    /// server responded with a message instead of its status
+    #[clap(long, conflicts_with_all = &["raw", "json", "watch"])]
+    check: bool,
+
     /// use verbose logging
     #[clap(long, short, parse(from_occurrences))]
     verbose: u32,
+
+    /// only print the requested data, without notices, warnings and logging
+    #[clap(long, short, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// ignore the defaults set in the config file
+    #[clap(long)]
+    no_defaults: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+enum Format {
+    Auto,
+    Human,
+    Json,
+    Csv,
+    Markdown,
+    Html,
+    Prometheus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+enum SampleFormat {
+    Formatted,
+    Plain,
+    Uuid,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+enum ImageProtocol {
+    Auto,
+    Kitty,
+    Iterm,
+    Blocks,
+}
+
+/// Normalized status output of the `--json` flag. Unlike the raw response,
+/// this has the same shape for every server.
+#[derive(Debug, Serialize)]
+struct JsonStatus {
+    online: u32,
+    max: u32,
+    ping_ms: u64,
+    version_name: String,
+    protocol: u32,
+    /// the description without formatting codes
+    description: String,
+    /// the names of the players in the sample
+    players: Vec<String>,
+    modded: bool,
+}
+
+/// an entry of the `--json` output with either the status of a server or the
+/// reason it couldn't be queried
+#[derive(Debug, Serialize)]
+struct JsonEntry<'a> {
+    address: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<JsonStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<JsonErrorKind>,
+    /// the full error message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+impl<'a> JsonEntry<'a> {
+    fn status(address: &'a str, status: JsonStatus) -> Self {
+        Self {
+            address,
+            status: Some(status),
+            error: None,
+            message: None,
+        }
+    }
+
+    fn error(address: &'a str, e: &miette::Report) -> Self {
+        Self {
+            address,
+            status: None,
+            error: Some(JsonErrorKind::of(e)),
+            message: Some(e.chain().join(": ")),
+        }
+    }
+}
+
+/// the reason a server couldn't be queried in the `--json` output
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum JsonErrorKind {
+    Timeout,
+    Dns,
+    Connection,
+    Parse,
+    ServerMessage,
+}
+
+impl JsonErrorKind {
+    fn of(e: &miette::Report) -> Self {
+        if e.downcast_ref::<TimeoutError>().is_some() {
+            return Self::Timeout;
+        }
+
+        match e.downcast_ref::<StatusError>() {
+            Some(StatusError::Resolve) => Self::Dns,
+            Some(StatusError::InvalidStatus(_)) => Self::Parse,
+            Some(StatusError::ServerMessage(_)) => Self::ServerMessage,
+            // everything else fails while talking to the server
+            None => Self::Connection,
+        }
+    }
+}
+
+/// the `--json` output when querying multiple servers
+#[derive(Debug, Serialize)]
+struct JsonBatch<'a> {
+    servers: Vec<JsonEntry<'a>>,
+    summary: Summary,
+}
+
+/// totals of a query of multiple servers
+#[derive(Debug, Default, Serialize)]
+struct Summary {
+    servers: usize,
+    online: usize,
+    offline: usize,
+    players: u64,
+}
+
+impl Summary {
+    /// adds a server to the summary, which is offline if it has no status
+    fn add(&mut self, online_players: Option<u32>) {
+        self.servers += 1;
+        match online_players {
+            Some(players) => {
+                self.online += 1;
+                self.players += u64::from(players);
+            },
+            None => self.offline += 1,
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} servers: {} online, {} offline, {} total players",
+            self.servers, self.online, self.offline, self.players
+        )
+    }
+}
+
+/// everything fetched from a single server
+struct Fetched {
+    raw_response: String,
+    pings: Vec<Duration>,
+    address: Option<ResolvedAddress>,
+    /// the full stat, if the query protocol was used
+    query: Option<FullStat>,
+    timing: Option<Timing>,
+}
+
+/// the parts of a status that are shown, extracted from the response so every
+/// output format works on the same data
+#[derive(Debug, Clone)]
+struct StatusSummary {
+    /// the description with formatting codes
+    description: String,
+    version: String,
+    protocol: u32,
+    online: u32,
+    max: u32,
+    /// the player counts as sent by the server. Unlike `online` and `max`,
+    /// these may be negative.
+    reported_online: i64,
+    reported_max: i64,
+    pings: Vec<Duration>,
+    /// the names and UUIDs of the players in the sample, as sent by the server
+    players: Vec<(String, String)>,
+    /// the base64 encoded favicon
+    favicon: Option<String>,
+    /// the mod IDs and versions sorted by mod ID, if the server has mods
+    mods: Option<Vec<(String, String)>>,
+    /// the forge channels and versions sorted by channel, if the server has
+    /// any
+    channels: Option<Vec<(String, String)>>,
+    /// the forge channels the client must have
+    required_channels: Vec<String>,
+    /// whether the server enforces secure chat, if it says so
+    secure_chat: Option<bool>,
+    /// whether the server previews chat messages, if it says so
+    chat_preview: Option<bool>,
+}
+
+impl StatusSummary {
+    /// whether the server runs forge, which is the case if it sends mods or
+    /// forge channels
+    fn modded(&self) -> bool {
+        self.mods.is_some() || self.channels.is_some()
+    }
+
+    /// the average ping, or zero if there are no samples
+    fn avg_ping(&self) -> Duration {
+        ping_stats(&self.pings)
+            .map(|(_, avg, _)| avg)
+            .unwrap_or_default()
+    }
+
+    /// whether a player count is negative, which is impossible
+    fn negative_count(&self) -> bool {
+        self.reported_online < 0 || self.reported_max < 0
+    }
+
+    /// whether the player count is impossible, which indicates that it is
+    /// faked
+    fn suspicious(&self) -> bool {
+        self.negative_count() || self.online > self.max
+    }
+}
+
+/// extracts the shown parts of a status. The description is passed separately,
+/// since it's converted from the raw response. Fields that aren't part of the
+/// status type are read from `raw_json`.
+fn summarize(
+    response: &StatusResponse,
+    raw_json: &Value,
+    description: String,
+    pings: &[Duration],
+) -> StatusSummary {
+    StatusSummary {
+        description,
+        version: response.version.name.clone(),
+        protocol: response.version.protocol,
+        online: response.players.online,
+        max: response.players.max,
+        reported_online: raw_json["players"]["online"]
+            .as_i64()
+            .unwrap_or_else(|| response.players.online.into()),
+        reported_max: raw_json["players"]["max"]
+            .as_i64()
+            .unwrap_or_else(|| response.players.max.into()),
+        pings: pings.to_vec(),
+        players: response
+            .players
+            .sample
+            .iter()
+            .flatten()
+            .map(|p| (p.name.clone(), p.id.to_string()))
+            .collect(),
+        favicon: response.favicon.clone(),
+        mods: mod_list(response).map(|list| list.map(owned_pair).collect()),
+        channels: channel_list(response).map(|list| list.map(owned_pair).collect()),
+        required_channels: raw_json["forgeData"]["channels"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|c| c["required"].as_bool() == Some(true))
+            .filter_map(|c| c["res"].as_str())
+            .map(str::to_string)
+            .collect(),
+        secure_chat: raw_json["enforcesSecureChat"].as_bool(),
+        chat_preview: raw_json["previewsChat"].as_bool(),
+    }
+}
+
+/// converts an entry of `mod_list` or `channel_list` to owned strings
+fn owned_pair((a, b): (&str, &str)) -> (String, String) {
+    (a.to_string(), b.to_string())
+}
+
+impl JsonStatus {
+    fn new(summary: &StatusSummary) -> Self {
+        Self {
+            online: summary.online,
+            max: summary.max,
+            ping_ms: summary.avg_ping().as_millis() as u64,
+            version_name: summary.version.clone(),
+            protocol: summary.protocol,
+            description: strip_mc_formatting(&summary.description),
+            players: summary
+                .players
+                .iter()
+                .map(|(name, _)| name.clone())
+                .collect(),
+            modded: summary.modded(),
+        }
+    }
 }
 
 impl Opt {
+    /// whether the raw response is printed instead of the status
+    fn is_raw(&self) -> bool {
+        self.raw || self.raw_pretty
+    }
+
+    /// returns the output format. `Format::Auto` is resolved, so it's never
+    /// returned.
+    fn format(&self) -> Format {
+        // these print their own output, regardless of the format
+        let other_output = self.is_raw() ||
+            self.check ||
+            self.compact ||
+            self.ping_only ||
+            self.players_only ||
+            self.icon_data ||
+            self.status_word ||
+            self.dump ||
+            self.watch.is_some() ||
+            self.output.is_some();
+
+        match self.format {
+            _ if self.json => Format::Json,
+            Format::Auto if other_output || io::stdout().is_tty() => Format::Human,
+            Format::Auto => Format::Json,
+            format => format,
+        }
+    }
+
+    fn fetch_options(&self) -> FetchOptions {
+        FetchOptions {
+            protocol_version: self.protocol_version,
+            timeout: self.timeout,
+            resolve_timeout: self.resolve_timeout.unwrap_or(self.timeout),
+            legacy: self.legacy,
+            bedrock: self.bedrock,
+            ping_count: self.count,
+            proxy: self.proxy.clone().map(|addr| Proxy {
+                addr,
+                auth: self.proxy_auth.clone(),
+            }),
+            bind: self.bind,
+            retries: self.retries,
+            no_srv: self.no_srv,
+            unix: self.unix.clone(),
+            resolver: self.resolver,
+            tls: self.tls.then(|| TlsOptions {
+                sni: self.tls_sni.clone(),
+                insecure: self.insecure,
+            }),
+        }
+    }
+
+    /// whether the table is printed with colors
+    fn use_color(&self) -> bool {
+        match (self.color, self.no_color, self.force_color) {
+            (Some(ColorChoice::Never), ..) | (_, true, _) => false,
+            (Some(ColorChoice::Always), ..) | (_, _, true) => true,
+            (Some(ColorChoice::Auto) | None, ..) => {
+                // see https://no-color.org
+                let no_color = env::var_os("NO_COLOR").map_or(false, |v| !v.is_empty());
+                self.output.is_none() && io::stdout().is_tty() && !no_color
+            },
+        }
+    }
+
+    /// the width to wrap the table to, if any
+    fn max_width(&self) -> Option<usize> {
+        self.width.map(|w| {
+            w.unwrap_or_else(|| {
+                terminal::size()
+                    .map(|(cols, _)| cols as usize)
+                    .unwrap_or(80)
+            })
+        })
+    }
+
     fn get_viuer_conf(&self) -> viuer::Config {
         let size = self.size.unwrap_or(16);
+        let (use_kitty, use_iterm) = match self.image_protocol {
+            ImageProtocol::Auto => (true, true),
+            ImageProtocol::Kitty => (true, false),
+            ImageProtocol::Iterm => (false, true),
+            ImageProtocol::Blocks => (false, false),
+        };
+
+        // viuer falls back to blocks if the protocol isn't supported
+        let protocol = if use_kitty && viuer::get_kitty_support() != viuer::KittySupport::None {
+            "kitty"
+        } else if use_iterm && viuer::is_iterm_supported() {
+            "iTerm"
+        } else {
+            "blocks"
+        };
+        info!("Printing favicon using {}", protocol);
+
         viuer::Config {
             transparent: true,
             absolute_offset: false,
             width: Some(size * 2),
             height: Some(size),
+            use_kitty,
+            use_iterm,
             ..viuer::Config::default()
         }
     }
 }
 
+fn parse_proxy_auth(s: &str) -> Result<(String, String), String> {
+    s.split_once(':')
+        .map(|(user, password)| (user.to_string(), password.to_string()))
+        .ok_or_else(|| "expected USER:PASSWORD".to_string())
+}
+
+/// parses a duration like `5s`, `500ms`, `1m` or `1h`. Bare numbers are
+/// milliseconds
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value = value
+        .parse::<u64>()
+        .map_err(|_| format!("invalid duration `{}`", s))?;
+
+    let secs = |factor: u64| {
+        value
+            .checked_mul(factor)
+            .map(Duration::from_secs)
+            .ok_or_else(|| format!("duration `{}` is too long", s))
+    };
+    match unit.trim() {
+        "" | "ms" => Ok(Duration::from_millis(value)),
+        "s" => Ok(Duration::from_secs(value)),
+        "m" => secs(60),
+        "h" => secs(60 * 60),
+        unit => Err(format!("unknown unit `{}`, expected ms, s, m or h", unit)),
+    }
+}
+
 #[tokio::main]
-async fn main() -> miette::Result<()> {
-    let opt = Opt::parse();
-
-    let log_level = match opt.verbose {
-        0 => Level::ERROR,
-        1 => Level::INFO,
-        2 => Level::DEBUG,
-        _ => Level::TRACE,
+async fn main() -> miette::Result<ExitCode> {
+    tokio::select! {
+        result = run() => result,
+        // if the handler can't be installed, this branch is disabled and `run` is
+        // awaited on its own
+        Ok(()) = signal::ctrl_c() => {
+            // the output may have been interrupted while formatting was applied
+            let _ = terminal::disable_raw_mode();
+            let mut stdout = io::stdout();
+            let _ = stdout.execute(ResetColor);
+            let _ = stdout.execute(SetAttribute(Attribute::Reset));
+            println!();
+            Ok(ExitCode::from(EXIT_INTERRUPTED))
+        },
+    }
+}
+
+async fn run() -> miette::Result<ExitCode> {
+    let config = Config::load()?;
+    let mut args = env::args_os().collect::<Vec<_>>();
+    let matches = Cli::command().get_matches_from(&args);
+
+    // the defaults of the config are only added for the arguments that aren't
+    // given and don't conflict with any that are. The flag a subcommand stands
+    // for counts as given. If a subcommand is given, they must come after it.
+    let cli = Cli::command();
+    let (cmd, cmd_matches, flag, defaults_pos) = match matches.subcommand() {
+        Some((name, sub_matches)) => match cli.find_subcommand(name) {
+            Some(sub) => (sub, sub_matches, Some(name), 2),
+            None => unreachable!("subcommand {} was parsed", name),
+        },
+        None => (&cli, &matches, None, args.len().min(1)),
+    };
+
+    let from_command_line =
+        |id: &str| cmd_matches.value_source(id) == Some(ValueSource::CommandLine);
+    if !from_command_line("no_defaults") {
+        let given = cmd
+            .get_arguments()
+            .filter(|arg| from_command_line(arg.get_id()) || Some(arg.get_id()) == flag)
+            .collect::<Vec<_>>();
+        let defaults = config.args(cmd, &given)?;
+        args.splice(defaults_pos..defaults_pos, defaults);
+    }
+
+    let matches = Cli::command().get_matches_from(args);
+    check_subcommand_conflicts(&matches);
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let mut opt = cli.command.map_or(cli.opt, Command::into_opt);
+
+    if opt.ip.is_empty() && opt.file.is_none() && !opt.discover {
+        Cli::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "The address of a server, --file or --discover is required",
+            )
+            .exit();
+    }
+
+    if !opt.quiet {
+        let log_level = match opt.verbose {
+            0 => Level::ERROR,
+            1 => Level::INFO,
+            2 => Level::DEBUG,
+            _ => Level::TRACE,
+        };
+
+        tracing_subscriber::fmt()
+            .compact()
+            .with_max_level(log_level)
+            .init();
+    }
+
+    if let Some(path) = &opt.file {
+        let servers = read_server_list(path)?;
+        opt.ip.extend(servers);
+
+        if opt.ip.is_empty() {
+            bail!("The server list {} is empty", path.display());
+        }
+    }
+
+    // everything but errors and warnings goes to the output file if one is given
+    let mut out: Box<dyn Write> = match &opt.output {
+        Some(path) => Box::new(OutputFile::new(path.clone())),
+        None => Box::new(io::stdout()),
+    };
+
+    if opt.discover {
+        let servers = discovery::discover(opt.timeout).await?;
+        if servers.is_empty() {
+            writeln!(out, "No LAN worlds found").into_diagnostic()?;
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        let mut table = Table::new();
+        for server in &servers {
+            table.small_entry(server.addr, McFormatContent(server.motd.clone()));
+        }
+        table.color = opt.use_color();
+        table.print(&mut out).into_diagnostic()?;
+
+        if !opt.discover_query {
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        writeln!(out).into_diagnostic()?;
+        opt.ip
+            .extend(servers.iter().map(|server| server.addr.to_string()));
+    }
+
+    for ip in &mut opt.ip {
+        let addr = config.resolve_alias(ip);
+        if addr != ip.as_str() {
+            info!("Using address {} of server {}", addr, ip);
+            *ip = addr.to_string();
+        }
+    }
+
+    if let Some(interval) = opt.watch {
+        watch(&opt, Duration::from_secs(interval.max(1))).await?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let fetch_options = opt.fetch_options();
+    let results = fetch_all(&opt, &fetch_options).await;
+
+    if opt.ip.len() > 1 && opt.format() == Format::Json && !opt.is_raw() {
+        return print_json_array(&opt, results, &mut out);
+    }
+
+    if opt.format() == Format::Prometheus && !opt.is_raw() {
+        return print_prometheus(&opt, results, &mut out);
+    }
+
+    if opt.compact {
+        return print_compact(&opt, results, &mut out);
+    }
+
+    if opt.status_word {
+        return print_status_words(results, &mut out);
+    }
+
+    // headers would make the output invalid JSON
+    let headers = opt.ip.len() > 1 && !opt.check && opt.format() != Format::Json;
+
+    let mut exit_code = 0;
+    let mut summary = Summary::default();
+    for (ip, result) in opt.ip.iter().zip(results) {
+        if headers {
+            writeln!(out, "==> {} <==", ip).into_diagnostic()?;
+        }
+
+        let online = result
+            .as_ref()
+            .ok()
+            .and_then(|fetched| json_status(fetched).ok())
+            .map(|status| status.online);
+
+        let code = match result
+            .and_then(|fetched| print_status(&opt, ip, fetched, true, None, &mut out))
+        {
+            Ok(code) => {
+                summary.add(Some(online.unwrap_or_default()));
+                code
+            },
+            Err(e) => {
+                summary.add(None);
+                let code = error_exit_code(&e);
+                if opt.check {
+                    writeln!(out, "{}: down - {}", ip, e).into_diagnostic()?;
+                } else if opt.format() == Format::Json && !opt.is_raw() {
+                    let entry = JsonEntry::error(ip, &e);
+                    writeln!(out, "{}", serde_json::to_string(&entry).into_diagnostic()?)
+                        .into_diagnostic()?;
+                } else if opt.ip.len() == 1 {
+                    // with a single server, errors are simply returned, unless they have
+                    // their own exit code
+                    if code == 1 {
+                        return Err(e);
+                    }
+                    eprintln!("{:?}", e);
+                } else {
+                    eprintln!("{:?}", e.wrap_err(format!("Failed to query {}", ip)));
+                }
+                code
+            },
+        };
+        exit_code = exit_code.max(code);
+
+        if headers {
+            writeln!(out).into_diagnostic()?;
+        }
+    }
+
+    if opt.ip.len() > 1 && !opt.check && !opt.quiet {
+        if opt.format() == Format::Human {
+            writeln!(out, "{}", summary).into_diagnostic()?;
+        } else if summary.offline > 0 {
+            eprintln!(
+                "{} of {} servers could not be queried",
+                summary.offline, summary.servers
+            );
+        }
+    }
+
+    Ok(ExitCode::from(exit_code))
+}
+
+/// the file given by `--output`. It is only created once something is written
+/// to it, so no empty file is left behind if nothing is printed.
+struct OutputFile {
+    path: PathBuf,
+    file: Option<File>,
+}
+
+impl OutputFile {
+    fn new(path: PathBuf) -> Self {
+        Self { path, file: None }
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => {
+                let file = File::create(&self.path).map_err(|e| {
+                    io::Error::new(
+                        e.kind(),
+                        format!(
+                            "Failed to create output file {}: {}",
+                            self.path.display(),
+                            e
+                        ),
+                    )
+                })?;
+                self.file.insert(file)
+            },
+        };
+        file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.as_mut().map_or(Ok(()), Write::flush)
+    }
+}
+
+/// reads the addresses in a server list file, one per line
+fn read_server_list(path: &Path) -> miette::Result<Vec<String>> {
+    let list = fs::read_to_string(path)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Failed to read server list {}", path.display()))?;
+
+    Ok(list
+        .lines()
+        .filter_map(|l| l.split('#').next())
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// prints the status of all servers as a single JSON object with a summary,
+/// returning the exit code. Errors are included in the output instead of
+/// aborting.
+fn print_json_array(
+    opt: &Opt,
+    results: Vec<miette::Result<Fetched>>,
+    out: &mut dyn Write,
+) -> miette::Result<ExitCode> {
+    let mut exit_code = 0;
+    let mut summary = Summary::default();
+    let servers = opt
+        .ip
+        .iter()
+        .zip(results)
+        .map(
+            |(ip, result)| match result.and_then(|fetched| json_status(&fetched)) {
+                Ok(status) => {
+                    summary.add(Some(status.online));
+                    JsonEntry::status(ip, status)
+                },
+                Err(e) => {
+                    summary.add(None);
+                    exit_code = exit_code.max(error_exit_code(&e));
+                    JsonEntry::error(ip, &e)
+                },
+            },
+        )
+        .collect::<Vec<_>>();
+
+    let batch = JsonBatch { servers, summary };
+    writeln!(out, "{}", serde_json::to_string(&batch).into_diagnostic()?).into_diagnostic()?;
+    Ok(ExitCode::from(exit_code))
+}
+
+/// prints one line per server with its most important information, returning
+/// the exit code
+fn print_compact(
+    opt: &Opt,
+    results: Vec<miette::Result<Fetched>>,
+    out: &mut dyn Write,
+) -> miette::Result<ExitCode> {
+    let mut exit_code = 0;
+    let mut summary = Summary::default();
+    let mut table = CompactTable::new();
+    for (ip, result) in opt.ip.iter().zip(results) {
+        match result.and_then(|fetched| json_status(&fetched)) {
+            Ok(status) => {
+                summary.add(Some(status.online));
+                table.row(vec![
+                    ip.clone(),
+                    format!("{}/{}", status.online, status.max),
+                    format!("{} ms", status.ping_ms),
+                    strip_mc_formatting(&status.version_name),
+                ]);
+            },
+            Err(e) => {
+                summary.add(None);
+                exit_code = exit_code.max(error_exit_code(&e));
+                eprintln!("{:?}", e.wrap_err(format!("Failed to query {}", ip)));
+                table.row(vec![ip.clone(), "offline".to_string()]);
+            },
+        }
+    }
+
+    table.print(out).into_diagnostic()?;
+    if opt.ip.len() > 1 {
+        writeln!(out, "{}", summary).into_diagnostic()?;
+    }
+    Ok(ExitCode::from(exit_code))
+}
+
+/// prints the status of all servers as Prometheus metrics, returning the exit
+/// code
+fn print_prometheus(
+    opt: &Opt,
+    results: Vec<miette::Result<Fetched>>,
+    out: &mut dyn Write,
+) -> miette::Result<ExitCode> {
+    let mut exit_code = 0;
+    let statuses = opt
+        .ip
+        .iter()
+        .zip(results)
+        .map(|(ip, result)| {
+            let status = match result.and_then(|fetched| json_status(&fetched)) {
+                Ok(status) => Some(status),
+                Err(e) => {
+                    exit_code = exit_code.max(error_exit_code(&e));
+                    eprintln!("{:?}", e.wrap_err(format!("Failed to query {}", ip)));
+                    None
+                },
+            };
+            (ip.as_str(), status)
+        })
+        .collect::<Vec<_>>();
+
+    let statuses = statuses
+        .iter()
+        .map(|(ip, status)| (*ip, status.as_ref()))
+        .collect::<Vec<_>>();
+    write!(out, "{}", prometheus_metrics(&statuses)).into_diagnostic()?;
+    Ok(ExitCode::from(exit_code))
+}
+
+/// formats the status of the servers as Prometheus metrics, labeled with their
+/// address. Servers without a status are reported as down.
+fn prometheus_metrics(statuses: &[(&str, Option<&JsonStatus>)]) -> String {
+    let label = |ip: &str| {
+        ip.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
     };
 
-    tracing_subscriber::fmt()
-        .compact()
-        .with_max_level(log_level)
-        .init();
+    let mut out = String::new();
+    let mut metric = |name: &str, help: &str, value: &dyn Fn(&JsonStatus) -> u64| {
+        out.push_str(&format!(
+            "# HELP {} {}\n# TYPE {} gauge\n",
+            name, help, name
+        ));
+        for (ip, status) in statuses {
+            if let Some(status) = status {
+                out.push_str(&format!(
+                    "{}{{server=\"{}\"}} {}\n",
+                    name,
+                    label(ip),
+                    value(status)
+                ));
+            }
+        }
+    };
+
+    metric(
+        "minecraft_players_online",
+        "Number of players online.",
+        &|s| s.online.into(),
+    );
+    metric(
+        "minecraft_players_max",
+        "Maximum number of players.",
+        &|s| s.max.into(),
+    );
+    metric(
+        "minecraft_ping_milliseconds",
+        "Ping to the server in milliseconds.",
+        &|s| s.ping_ms,
+    );
+
+    out.push_str("# HELP minecraft_up Whether the server could be queried.\n");
+    out.push_str("# TYPE minecraft_up gauge\n");
+    for (ip, status) in statuses {
+        out.push_str(&format!(
+            "minecraft_up{{server=\"{}\"}} {}\n",
+            label(ip),
+            u8::from(status.is_some())
+        ));
+    }
+
+    out
+}
+
+/// parses a fetched status into the `--json` output format
+fn json_status(fetched: &Fetched) -> miette::Result<JsonStatus> {
+    let (raw_json, response) = parse_status(&fetched.raw_response)?;
+
+    match response {
+        EitherStatusResponse::Text { text } => Err(StatusError::ServerMessage(text).into()),
+        EitherStatusResponse::Normal(response) => {
+            let description = chat_component_to_mc_formatted(&raw_json["description"]);
+            let summary = summarize(&response, &raw_json, description, &fetched.pings);
+            Ok(JsonStatus::new(&summary))
+        },
+    }
+}
+
+/// prints `up` or `down` for each server, returning the exit code of the
+/// first server that is down
+fn print_status_words(
+    results: Vec<miette::Result<Fetched>>,
+    out: &mut dyn Write,
+) -> miette::Result<ExitCode> {
+    let mut exit_code = 0;
+    for result in results {
+        match result.and_then(|fetched| json_status(&fetched)) {
+            Ok(_) => writeln!(out, "up").into_diagnostic()?,
+            Err(e) => {
+                info!("Server is down: {:?}", e);
+                if exit_code == 0 {
+                    exit_code = error_exit_code(&e);
+                }
+                writeln!(out, "down").into_diagnostic()?;
+            },
+        }
+    }
+    Ok(ExitCode::from(exit_code))
+}
+
+/// returns the exit code for an error that occurred while querying a server
+fn error_exit_code(e: &miette::Report) -> u8 {
+    if e.downcast_ref::<TimeoutError>().is_some() {
+        return EXIT_TIMEOUT;
+    }
+
+    match e.downcast_ref::<StatusError>() {
+        Some(StatusError::ServerMessage(_)) => EXIT_TEXT_RESPONSE,
+        _ => 1,
+    }
+}
 
-    let (addr, port) = resolve_address(&opt.ip)
-        .await
-        .wrap_err("Error resolving address")?;
+/// queries the servers every `interval` and reprints their status, until
+/// interrupted. If stdin is a terminal, the interval can be changed with keys.
+async fn watch(opt: &Opt, mut interval: Duration) -> miette::Result<()> {
+    let fetch_options = opt.fetch_options();
+    let mut stdout = io::stdout();
+    let keys = io::stdin().is_tty();
+    let mut last_success = vec![None; opt.ip.len()];
+    // the player counts of the last poll, to show how they changed
+    let mut last_online = vec![None; opt.ip.len()];
+    // the most recent pings of each server, shown as a sparkline
+    let mut ping_history = vec![VecDeque::with_capacity(PING_HISTORY_LEN); opt.ip.len()];
+    // the row the status is printed at. The favicons are only printed once above
+    // it to avoid flickering.
+    let mut status_row = None;
 
-    info!("Using address '{}:{}'", &addr, &port);
+    loop {
+        let start = time::Instant::now();
+        let results = fetch_all(opt, &fetch_options).await;
 
-    let config = ConnectionConfig::build(addr)
-        .with_port(port)
-        .with_protocol_version(opt.protocol_version);
+        let row = match status_row {
+            Some(row) => row,
+            None => {
+                stdout.execute(Clear(ClearType::All)).into_diagnostic()?;
+                stdout.execute(MoveTo(0, 0)).into_diagnostic()?;
 
-    // create timeout for server connection
-    let (raw_response, ping) = time::timeout(Duration::from_millis(opt.timeout), async {
-        info!("Connecting to server");
-        let mut con = config.connect().await.into_diagnostic()?;
+                let mut height = 0;
+                if opt.image {
+                    for fetched in results.iter().flatten() {
+                        // invalid responses are reported with the status below
+                        let raw_json = match parse_status(&fetched.raw_response) {
+                            Ok((json, _)) => json,
+                            Err(_) => continue,
+                        };
+                        if let Some(favicon) = raw_json["favicon"].as_str() {
+                            let img = parse_base64_image(favicon.to_string(), opt.max_icon_bytes)?;
+                            height += print_favicon(opt, &img)?;
+                        }
+                    }
+                }
+
+                *status_row.insert(height as u16)
+            },
+        };
+
+        stdout.execute(MoveTo(0, row)).into_diagnostic()?;
+        stdout
+            .execute(Clear(ClearType::FromCursorDown))
+            .into_diagnostic()?;
+
+        for ((((ip, result), last), online), history) in opt
+            .ip
+            .iter()
+            .zip(results)
+            .zip(&mut last_success)
+            .zip(&mut last_online)
+            .zip(&mut ping_history)
+        {
+            if opt.ip.len() > 1 {
+                println!("==> {} <==", ip);
+            }
 
-        info!("Requesting status");
-        let status = con.status_raw().await.into_diagnostic()?;
-        let ping = con.ping().await.into_diagnostic()?;
+            let previous = *online;
+            let result = result.and_then(|fetched| {
+                *online = online_players(&fetched);
+                if let Some((_, avg, _)) = ping_stats(&fetched.pings) {
+                    if history.len() == PING_HISTORY_LEN {
+                        history.pop_front();
+                    }
+                    history.push_back(avg);
+                }
+                print_status(opt, ip, fetched, false, previous, &mut io::stdout())
+            });
+            match result {
+                Ok(_) => *last = Some(SystemTime::now()),
+                Err(e) => {
+                    println!("{} is offline", ip);
+                    info!("Failed to query {}: {:?}", ip, e);
+                },
+            }
 
-        Result::<_, miette::Error>::Ok((status, ping))
+            if let Some(time) = last {
+                println!("Last successful poll: {}", format_utc_time(*time));
+            }
+            if !history.is_empty() {
+                println!("Ping history: {}", sparkline(history.make_contiguous()));
+            }
+            println!();
+        }
+
+        if !keys {
+            time::sleep_until(start + interval).await;
+            continue;
+        }
+
+        if !opt.quiet {
+            println!(
+                "q: quit, r: refresh, +/-: change interval ({} s)",
+                interval.as_secs()
+            );
+        }
+
+        // wait for the next poll, unless a key requests it earlier
+        loop {
+            let remaining = (start + interval).saturating_duration_since(time::Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+
+            match read_watch_key(remaining).await? {
+                Some(WatchKey::Quit) => return Ok(()),
+                Some(WatchKey::Refresh) => break,
+                Some(WatchKey::Longer) => interval += Duration::from_secs(1),
+                Some(WatchKey::Shorter) => {
+                    interval = interval
+                        .saturating_sub(Duration::from_secs(1))
+                        .max(Duration::from_secs(1))
+                },
+                None => {},
+            }
+        }
+    }
+}
+
+/// an action requested by pressing a key in watch mode
+enum WatchKey {
+    Quit,
+    Refresh,
+    /// increase the interval
+    Longer,
+    /// decrease the interval
+    Shorter,
+}
+
+/// waits up to `timeout` for a key press in watch mode. The terminal is only
+/// in raw mode while waiting, so the status can be printed normally.
+async fn read_watch_key(timeout: Duration) -> miette::Result<Option<WatchKey>> {
+    tokio::task::spawn_blocking(move || {
+        terminal::enable_raw_mode()?;
+        let key = poll_watch_key(timeout);
+        terminal::disable_raw_mode()?;
+        key
     })
     .await
+    .into_diagnostic()?
     .into_diagnostic()
-    .context("Connection to server timed out.")??;
+    .wrap_err("Failed to read key")
+}
+
+fn poll_watch_key(timeout: Duration) -> io::Result<Option<WatchKey>> {
+    if !event::poll(timeout)? {
+        return Ok(None);
+    }
+
+    let key = match event::read()? {
+        Event::Key(key) => key,
+        _ => return Ok(None),
+    };
+
+    Ok(match key.code {
+        // raw mode turns Ctrl-C into a key press
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Some(WatchKey::Quit),
+        KeyCode::Char('q') | KeyCode::Esc => Some(WatchKey::Quit),
+        KeyCode::Char('r') => Some(WatchKey::Refresh),
+        KeyCode::Char('+') => Some(WatchKey::Longer),
+        KeyCode::Char('-') => Some(WatchKey::Shorter),
+        _ => None,
+    })
+}
+
+/// returns the number of online players from a fetched status, if it is valid
+fn online_players(fetched: &Fetched) -> Option<u32> {
+    match parse_status(&fetched.raw_response).ok()?.1 {
+        EitherStatusResponse::Normal(response) => Some(response.players.online),
+        EitherStatusResponse::Text { .. } => None,
+    }
+}
+
+/// formats the time of day of `time` in UTC
+fn format_utc_time(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() %
+        86400;
+    format!(
+        "{:02}:{:02}:{:02} UTC",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// fetches all servers, with at most `--concurrency` queries at once. The
+/// results are in the same order as the servers.
+async fn fetch_all(opt: &Opt, fetch_options: &FetchOptions) -> Vec<miette::Result<Fetched>> {
+    let semaphore = Semaphore::new(opt.concurrency.max(1));
+    join_all(opt.ip.iter().map(|ip| async {
+        // the semaphore is never closed
+        let _permit = semaphore.acquire().await;
+        fetch(opt, fetch_options, ip).await
+    }))
+    .await
+}
+
+/// fetches the status of the server at `ip`, and the full stat if requested
+async fn fetch(opt: &Opt, fetch_options: &FetchOptions, ip: &str) -> miette::Result<Fetched> {
+    let RawStatus {
+        response: raw_response,
+        pings,
+        address,
+        timing,
+    } = fetch_raw_status(ip, fetch_options).await?;
 
+    let query = match (opt.query, &address) {
+        (true, Some(address)) => {
+            let stat = time::timeout(
+                fetch_options.timeout,
+                query::full_stat(&address.host, address.port, fetch_options.bind),
+            )
+            .await
+            .map_err(|_| TimeoutError("Query"))?
+            .wrap_err("Query failed. Is enable-query set on the server?")?;
+            Some(stat)
+        },
+        _ => None,
+    };
+
+    Ok(Fetched {
+        raw_response,
+        pings,
+        address,
+        query,
+        timing,
+    })
+}
+
+/// prints the status of the server at `ip` in the format requested by `opt`,
+/// returning the exit code.
+///
+/// the favicon is only printed if `show_image` is set. If `previous_online` is
+/// given, the change of the player count is shown.
+fn print_status(
+    opt: &Opt,
+    ip: &str,
+    fetched: Fetched,
+    show_image: bool,
+    previous_online: Option<u32>,
+    out: &mut dyn Write,
+) -> miette::Result<u8> {
     if opt.raw {
-        println!("{}", raw_response);
-        return Ok(());
+        writeln!(out, "{}", fetched.raw_response).into_diagnostic()?;
+        return Ok(0);
     }
 
-    info!("Parsing status");
-    let response = serde_json::from_str::<EitherStatusResponse>(&raw_response).into_diagnostic()?;
+    if opt.raw_pretty {
+        match serde_json::from_str::<Value>(&fetched.raw_response) {
+            Ok(json) => writeln!(
+                out,
+                "{}",
+                serde_json::to_string_pretty(&json).into_diagnostic()?
+            )
+            .into_diagnostic()?,
+            Err(e) => {
+                if !opt.quiet {
+                    eprintln!(
+                        "Warning: the response is not valid JSON ({}), printing it as-is",
+                        e
+                    );
+                }
+                writeln!(out, "{}", fetched.raw_response).into_diagnostic()?;
+            },
+        }
+        return Ok(0);
+    }
+
+    if opt.ping_only {
+        let ping = ping_stats(&fetched.pings)
+            .map(|(_, avg, _)| avg)
+            .unwrap_or_default();
+        writeln!(out, "{}", ping.as_millis()).into_diagnostic()?;
+        return Ok(0);
+    }
+
+    info!("Parsing status of {}", ip);
+    let (raw_json, response) = parse_status(&fetched.raw_response)?;
 
     let response = match response {
         EitherStatusResponse::Text { text } => {
-            println!("The server says:\n{}", text);
+            if opt.check {
+                writeln!(out, "{}: maintenance - {}", ip, strip_control_chars(&text))
+                    .into_diagnostic()?;
+            } else if opt.format() == Format::Json {
+                let e: miette::Report = StatusError::ServerMessage(text).into();
+                let entry = JsonEntry::error(ip, &e);
+                writeln!(out, "{}", serde_json::to_string(&entry).into_diagnostic()?)
+                    .into_diagnostic()?;
+            } else {
+                writeln!(out, "The server says:\n{}", strip_control_chars(&text))
+                    .into_diagnostic()?;
+            }
 
-            return Ok(());
+            return Ok(EXIT_TEXT_RESPONSE);
         },
         EitherStatusResponse::Normal(r) => r,
     };
 
+    let mut description = chat_component_to_mc_formatted(&raw_json["description"]);
+    if opt.amp_codes {
+        description = amp_codes_to_section(&description);
+    }
+
+    let summary = summarize(&response, &raw_json, description, &fetched.pings);
+
+    if opt.dump {
+        writeln!(out, "{:#?}", response).into_diagnostic()?;
+
+        let unparsed = raw_json
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter(|(key, _)| !PARSED_FIELDS.contains(&key.as_str()))
+            .collect::<Vec<_>>();
+        if !unparsed.is_empty() {
+            writeln!(out, "\nUnparsed fields:\n{:#?}", unparsed).into_diagnostic()?;
+        }
+        return Ok(0);
+    }
+
+    if opt.players_only {
+        // the query protocol has the full list, not just a sample
+        let players = match &fetched.query {
+            Some(query) => order_players(opt, query.players.clone()),
+            None => player_sample(opt, &summary),
+        };
+        for player in players {
+            writeln!(
+                out,
+                "{}",
+                strip_control_chars(&strip_mc_formatting(&player))
+            )
+            .into_diagnostic()?;
+        }
+        return Ok(0);
+    }
+
+    if opt.icon_data {
+        let favicon = summary
+            .favicon
+            .as_ref()
+            .ok_or_else(|| miette!("The server has no favicon"))?;
+        writeln!(out, "{}", favicon).into_diagnostic()?;
+        return Ok(0);
+    }
+
+    if opt.check {
+        let ping = ping_stats(&fetched.pings)
+            .map(|(_, avg, _)| avg)
+            .unwrap_or_default();
+        writeln!(
+            out,
+            "{}: up - {}/{} players, {} ms",
+            ip,
+            summary.online,
+            summary.max,
+            ping.as_millis()
+        )
+        .into_diagnostic()?;
+        return Ok(0);
+    }
+
+    if let Some(path) = &opt.save_icon {
+        let favicon = summary
+            .favicon
+            .clone()
+            .ok_or_else(|| miette!("The server has no favicon to save"))?;
+        save_image(&parse_base64_image(favicon, opt.max_icon_bytes)?, path)?;
+    }
+
+    match opt.format() {
+        Format::Auto | Format::Human | Format::Markdown | Format::Html => {},
+        Format::Json => {
+            let status = JsonStatus::new(&summary);
+            writeln!(out, "{}", serde_json::to_string(&status).into_diagnostic()?)
+                .into_diagnostic()?;
+            return Ok(0);
+        },
+        Format::Prometheus => {
+            let status = JsonStatus::new(&summary);
+            write!(out, "{}", prometheus_metrics(&[(ip, Some(&status))])).into_diagnostic()?;
+            return Ok(0);
+        },
+        Format::Csv => return print_csv(opt, &summary, out).map(|_| 0),
+    }
+
+    let human = opt.format() == Format::Human;
+
+    if summary.negative_count() && !opt.quiet {
+        eprintln!(
+            "Warning: the server reports a negative player count ({}/{}). The player count may be \
+             fake.",
+            summary.reported_online, summary.reported_max
+        );
+    } else if summary.suspicious() && !opt.quiet {
+        eprintln!(
+            "Warning: the server reports more online players than it allows ({}/{}). The player \
+             count may be fake.",
+            summary.online, summary.max
+        );
+    }
+
+    // some servers send an empty status if they don't like the protocol version
+    let empty =
+        summary.description.is_empty() && summary.version.is_empty() && summary.players.is_empty();
+    if empty && opt.protocol_version != ProtocolVersion::Auto && !opt.quiet {
+        eprintln!(
+            "Hint: the server sent an empty status. It may not support protocol version {}, try \
+             `--protocol auto`.",
+            opt.protocol_version
+        );
+    }
+
+    // the server may answer differently if it isn't pinged with its own version
+    if let (ProtocolVersion::Number(requested), Some(expected), false) = (
+        opt.protocol_version,
+        protocol_versions::protocol_of(&summary.version),
+        opt.quiet,
+    ) {
+        if requested as u32 != expected && summary.protocol != requested as u32 {
+            eprintln!(
+                "Note: the server seems to run {} (protocol {}), but protocol {} was requested. \
+                 Try `--protocol {}` if the status looks wrong.",
+                protocol_versions::version_name(expected).unwrap_or_default(),
+                expected,
+                requested,
+                expected
+            );
+        }
+    }
+
     // if the server has mods, and the user hasn't used the -m argument, notify
     // that.
-    if let (false, Some(_)) = (opt.mods, response.forge_mod_info()) {
-        println!("This server has mods. To show them use the -m argument\n")
+    if let (false, Some(mods), true) = (opt.mods, &summary.mods, human && !opt.quiet) {
+        writeln!(
+            out,
+            "This server has {} mods. To show them use the -m argument\n",
+            mods.len()
+        )
+        .into_diagnostic()?
     }
 
-    format_table(
-        &response,
-        ping.as_millis(),
-        opt.mods,
-        opt.modversions,
-        opt.channels,
-    )
-    .stdout()
+    // the favicon is decoded once for every place it's shown in
+    let icon = match &summary.favicon {
+        Some(favicon) if opt.image || opt.icon_color => {
+            Some(parse_base64_image(favicon.clone(), opt.max_icon_bytes))
+        },
+        _ => None,
+    };
+
+    let mut table = format_table(opt, &summary, icon.as_ref(), &fetched, previous_online);
+    table.color = opt.use_color();
+    table.max_width = opt.max_width();
+    table.color_keys = opt.color_keys;
+    table.dividers = opt.dividers;
+    table.big_border_char = opt.border;
+    table.max_value_width = opt.truncate.filter(|&w| w > 0);
+
+    match opt.format() {
+        Format::Markdown => table.print_markdown(out),
+        Format::Html => table.print_html(out),
+        _ => table.print(out),
+    }
     .into_diagnostic()?;
 
-    if let (Some(img), true) = (response.favicon, opt.image) {
-        let decoded = parse_base64_image(img)?;
-        viuer::print(&decoded, &opt.get_viuer_conf()).into_diagnostic()?;
+    // the image can only be printed to the terminal
+    if let (Some(icon), true) = (icon, opt.image && show_image && opt.output.is_none()) {
+        print_favicon(opt, &icon?)?;
+    }
+    Ok(0)
+}
+
+/// prints the mod list and forge channels of the server as CSV
+fn print_csv(opt: &Opt, summary: &StatusSummary, out: &mut dyn Write) -> miette::Result<()> {
+    if !opt.mods && !opt.channels {
+        bail!("CSV output requires --mods or --channels");
+    }
+
+    if opt.mods {
+        let mods = summary.mods.iter().flatten().map(|(a, b)| (&**a, &**b));
+        write!(
+            out,
+            "{}",
+            get_table(mods, ListFormat::Csv(["modid", "version"]))
+        )
+        .into_diagnostic()?;
     }
+
+    if opt.mods && opt.channels {
+        writeln!(out).into_diagnostic()?;
+    }
+
+    if opt.channels {
+        let channels = summary.channels.iter().flatten().map(|(a, b)| (&**a, &**b));
+        write!(
+            out,
+            "{}",
+            get_table(channels, ListFormat::Csv(["channel", "version"]))
+        )
+        .into_diagnostic()?;
+    }
+
     Ok(())
 }
 
+/// prints a decoded favicon, returning its height
+fn print_favicon(opt: &Opt, favicon: &DynamicImage) -> miette::Result<u32> {
+    // show everything printed before immediately, even if printing the image is
+    // slow or fails
+    io::stdout().flush().into_diagnostic()?;
+
+    let (_, height) = viuer::print(favicon, &opt.get_viuer_conf()).into_diagnostic()?;
+    Ok(height)
+}
+
+/// returns the names of the player sample in the format requested by `opt`
+fn player_sample(opt: &Opt, summary: &StatusSummary) -> Vec<String> {
+    let players = summary
+        .players
+        .iter()
+        .map(|(name, id)| {
+            let name = if opt.amp_codes {
+                amp_codes_to_section(name)
+            } else {
+                name.clone()
+            };
+
+            match opt.sample_format {
+                SampleFormat::Formatted => name,
+                SampleFormat::Plain => strip_mc_formatting(&name),
+                SampleFormat::Uuid => format!("{} ({})", name, id),
+            }
+        })
+        .collect();
+
+    order_players(opt, players)
+}
+
+/// sorts and deduplicates a list of players, if requested by `opt`
+fn order_players(opt: &Opt, mut players: Vec<String>) -> Vec<String> {
+    if opt.sort_players {
+        players.sort_by_cached_key(|p| p.to_lowercase());
+    }
+
+    if opt.unique_players {
+        players = players.into_iter().unique().collect();
+    }
+
+    players
+}
+
 fn format_table(
-    response: &StatusResponse,
-    ping: u128,
-    mods: bool,
-    modversions: bool,
-    channels: bool,
+    opt: &Opt,
+    summary: &StatusSummary,
+    icon: Option<&miette::Result<DynamicImage>>,
+    fetched: &Fetched,
+    previous_online: Option<u32>,
 ) -> Table {
+    let pings = &summary.pings;
+    let query = fetched.query.as_ref();
+
+    let mut players = player_sample(opt, summary);
+    let hidden = if opt.max_players > 0 && players.len() > opt.max_players {
+        let hidden = players.len() - opt.max_players;
+        players.truncate(opt.max_players);
+        Some(format!("... and {} more", hidden))
+    } else {
+        None
+    };
+
     // this syntax is used due to a nightly function which will be added to rust
     // also called intersperse
-    let player_sample = Itertools::intersperse(
-        response
-            .players
-            .sample
+    let player_sample = Itertools::intersperse(players.into_iter().chain(hidden), "\n".to_string())
+        .collect::<String>();
+
+    // whether the field with the given ID should be shown
+    let show = |field: &str| {
+        opt.fields
             .as_ref()
-            .unwrap_or(&vec![])
-            .iter()
-            .map(|p| p.name.as_str()),
-        "\n",
-    )
-    .collect::<String>();
+            .map_or(true, |fields| fields.iter().any(|f| f == field))
+    };
 
     let mut table = Table::new();
 
-    if let Some(s) = none_if_empty!(McFormatContent(response.description.get_text().clone())) {
+    if let (Some(s), true) = (
+        none_if_empty!(McFormatContent(summary.description.clone())),
+        show("description"),
+    ) {
         table.big_entry("Description", s);
     }
 
-    if let ServerDescription::Big(big_desc) = &response.description {
-        let desc = &big_desc.extra;
-        let txt = desc.iter().map(|p| p.text.clone()).collect::<String>();
-        if let Some(s) = none_if_empty!(txt) {
-            table.big_entry("Extra Description", McFormatContent(s));
+    if show("sample") {
+        if let Some(s) = none_if_empty!(McFormatContent(player_sample)) {
+            table.big_entry("Player Sample", s);
+        } else if summary.online > 0 {
+            table.small_entry("Player Sample", "(hidden by server)".to_string());
         }
     }
 
-    if let Some(s) = none_if_empty!(McFormatContent(player_sample)) {
-        table.big_entry("Player Sample", s);
+    if let Some(query) = query {
+        if let (Some(s), true) = (
+            none_if_empty!(McFormatContent(query.players.join("\n"))),
+            show("players"),
+        ) {
+            table.big_entry("Players", s);
+        }
+
+        if let (Some(s), true) = (none_if_empty!(query.plugins().join("\n")), show("plugins")) {
+            table.big_entry("Plugins", s);
+        }
     }
 
     table.blank();
 
-    if let Some(s) = none_if_empty!(response.version.name.clone()) {
+    if let (Some(address), true) = (
+        fetched
+            .address
+            .as_ref()
+            .filter(|a| a.method == ResolveMethod::Srv),
+        show("resolved"),
+    ) {
+        table.small_entry("Resolved", format!("{} (via SRV)", address));
+    }
+
+    if let (Some(s), true) = (none_if_empty!(summary.version.clone()), show("version")) {
         table.small_entry("Server Version", s);
     }
 
-    table.small_entry("Online Players", response.players.online.to_string());
-    table.small_entry("Max Players", response.players.max.to_string());
-    table.small_entry("Ping", ping.to_string());
-    table.small_entry("Protocol Version", response.version.protocol.to_string());
+    if let Some(query) = query {
+        if let (Some(s), true) = (query.server_mod(), show("software")) {
+            table.small_entry("Server Software", s.to_string());
+        }
+
+        if let (Some(s), true) = (query.game_type(), show("gametype")) {
+            table.small_entry("Game Type", s.to_string());
+        }
+
+        if let (Some(s), true) = (query.map(), show("map")) {
+            table.small_entry("Map", s.to_string());
+        }
+    }
+
+    if show("online") {
+        let online = summary.online;
+        let delta = previous_online.map_or(0, |p| i64::from(online) - i64::from(p));
+        let mut text = summary.reported_online.to_string();
+        if summary.suspicious() {
+            text.push_str(" (suspicious)");
+        }
+        table.small_entry("Online Players", DeltaValue::new(text, delta));
+    }
+
+    if show("max") {
+        table.small_entry("Max Players", summary.reported_max.to_string());
+    }
+
+    if let (Some((min, avg, max)), true) = (ping_stats(pings), show("ping")) {
+        let ping = if pings.len() > 1 {
+            format!(
+                "{}/{}/{} ms (min/avg/max)",
+                min.as_millis(),
+                avg.as_millis(),
+                max.as_millis()
+            )
+        } else {
+            avg.as_millis().to_string()
+        };
+        table.small_entry("Ping", ColoredValue::new(ping, ping_color(avg)));
+    }
+
+    if let (Some(jitter), true) = (ping_jitter(pings), show("ping")) {
+        table.small_entry("Jitter", format!("{} ms", jitter.as_millis()));
+    }
+
+    if let (Some(timing), true) = (fetched.timing, opt.timing_detail && show("ping")) {
+        table.small_entry("Connect Time", format!("{} ms", timing.connect.as_millis()));
+        table.small_entry("Status Time", format!("{} ms", timing.status.as_millis()));
+    }
+
+    if show("protocol") {
+        let protocol = summary.protocol;
+        let protocol = match (
+            opt.decode_protocol,
+            protocol_versions::version_name(protocol),
+        ) {
+            (true, Some(version)) => format!("{} ({})", protocol, version),
+            _ => protocol.to_string(),
+        };
+        table.small_entry("Protocol Version", protocol);
+    }
+
+    if show("chat") {
+        let flags = [
+            (summary.secure_chat, "Secure Chat Enforced"),
+            (summary.chat_preview, "Chat Preview"),
+        ];
+        for (flag, name) in flags {
+            if let Some(flag) = flag {
+                table.small_entry(name, if flag { "yes" } else { "no" });
+            }
+        }
+    }
+
+    if show("modded") {
+        table.small_entry("Modded", if summary.modded() { "yes" } else { "no" });
+    }
+
+    if let (Some(mods), true) = (&summary.mods, show("modcount")) {
+        table.small_entry("Mod Count", mods.len().to_string());
+    }
+
+    if let (Some(favicon), Some(icon), true) =
+        (&summary.favicon, icon, opt.image && show("favicon"))
+    {
+        let info = match (icon, base64_image_format(favicon)) {
+            (Ok(img), Ok(format)) => format!("{}x{} {}", img.width(), img.height(), format),
+            _ => "invalid".to_string(),
+        };
+        table.small_entry("Favicon", info);
+    }
+
+    if let (Some(icon), true) = (icon, opt.icon_color && show("favicon")) {
+        if let Ok(img) = icon {
+            let [r, g, b] = average_color(img);
+            table.small_entry(
+                "Icon Color",
+                ColoredValue::new(
+                    format!("██ #{:02x}{:02x}{:02x}", r, g, b),
+                    Color::Rgb { r, g, b },
+                ),
+            );
+        }
+    }
 
     table.blank();
 
-    if let (Some(mod_list), true) = (response.forge_mod_info(), mods) {
-        let txt = get_table(
-            mod_list
-                .iter()
-                .sorted_by(|a, b| a.modid.cmp(&b.modid))
-                .map(|m| (&*m.modid, &*m.version)),
-            modversions,
-        );
+    if let (Some(list), true) = (&summary.mods, opt.mods && show("mods")) {
+        let format = if opt.modversions {
+            ListFormat::Columns(Some(["Mod ID", "Version"]))
+        } else {
+            ListFormat::Names
+        };
+        let filter = opt.mod_filter.as_deref().map(str::to_lowercase);
+        let list = list
+            .iter()
+            .map(|(a, b)| (&**a, &**b))
+            .filter(|(modid, version)| {
+                filter.as_ref().map_or(true, |f| {
+                    modid.to_lowercase().contains(f) || version.to_lowercase().contains(f)
+                })
+            });
+        let txt = get_table(list, format);
 
-        if let Some(s) = none_if_empty!(txt) {
-            table.big_entry("Mods", s);
+        // mod IDs and versions are sent by the server, so formatting codes in them
+        // aren't applied
+        match (none_if_empty!(txt), &opt.mod_filter) {
+            (Some(s), _) if opt.modversions => table.big_entry("Mods", HeaderedContent(s)),
+            (Some(s), _) => table.big_entry("Mods", s),
+            (None, Some(pattern)) => {
+                table.small_entry("Mods", format!("no mods match {}", pattern))
+            },
+            (None, None) => {},
         }
     }
 
-    if let (true, Some(fd)) = (channels, &response.forge_data) {
-        let txt = get_table(
-            fd.channels
-                .iter()
-                .sorted_by(|a, b| a.res.cmp(&b.res))
-                .map(|c| (&*c.res, &*c.version)),
-            true,
-        );
+    if let (true, Some(list)) = (opt.channels && show("channels"), &summary.channels) {
+        let list = list
+            .iter()
+            .map(|(res, version)| {
+                let mut version = version.clone();
+                if summary.required_channels.contains(res) {
+                    version.push_str(" (required)");
+                }
+                (res.as_str(), version)
+            })
+            .collect::<Vec<_>>();
+        let list = list.iter().map(|(a, b)| (*a, &**b));
+        let txt = get_table(list, ListFormat::Columns(Some(["Channel", "Version"])));
 
         if let Some(s) = none_if_empty!(txt) {
-            table.big_entry("Forge Channels", s);
+            table.big_entry("Forge Channels", HeaderedContent(s));
         }
     }
 
     table
 }
+
+/// the standard deviation of the pings, or `None` if there are less than 2
+fn ping_jitter(pings: &[Duration]) -> Option<Duration> {
+    if pings.len() < 2 {
+        return None;
+    }
+
+    let secs = pings.iter().map(Duration::as_secs_f64);
+    let mean = secs.clone().sum::<f64>() / pings.len() as f64;
+    let variance = secs.map(|p| (p - mean).powi(2)).sum::<f64>() / pings.len() as f64;
+    Some(Duration::from_secs_f64(variance.sqrt()))
+}
+
+/// the color of a ping in the table, depending on how good it is
+fn ping_color(ping: Duration) -> Color {
+    match ping.as_millis() {
+        0..=99 => Color::Green,
+        100..=299 => Color::Yellow,
+        _ => Color::Red,
+    }
+}
+
+/// the mod IDs and versions of the server, sorted by mod ID
+fn mod_list(response: &StatusResponse) -> Option<impl Iterator<Item = (&str, &str)> + Clone + '_> {
+    Some(
+        response
+            .forge_mod_info()?
+            .iter()
+            .sorted_by(|a, b| a.modid.cmp(&b.modid))
+            .map(|m| (&*m.modid, &*m.version)),
+    )
+}
+
+/// the forge channels and their versions, sorted by channel
+fn channel_list(
+    response: &StatusResponse,
+) -> Option<impl Iterator<Item = (&str, &str)> + Clone + '_> {
+    Some(
+        response
+            .forge_data
+            .as_ref()?
+            .channels
+            .iter()
+            .sorted_by(|a, b| a.res.cmp(&b.res))
+            .map(|c| (&*c.res, &*c.version)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("500"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("3s"), Ok(Duration::from_secs(3)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+        assert!(parse_duration("1d").is_err());
+    }
+
+    #[test]
+    fn rejects_overflowing_durations() {
+        assert!(parse_duration(&format!("{}h", u64::MAX / 60)).is_err());
+        assert!(parse_duration(&format!("{}m", u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn summarizes_status() {
+        let raw = r#"{
+            "version": { "name": "Paper 1.19.4", "protocol": 762 },
+            "players": {
+                "online": 30,
+                "max": 20,
+                "sample": [{ "name": "Notch", "id": "069a79f4-44e9-4726-a5be-fca90e38aaf5" }]
+            },
+            "description": { "text": "A server" },
+            "favicon": "data:image/png;base64,iVBORw0K",
+            "enforcesSecureChat": true
+        }"#;
+        let (raw_json, response) = match parse_status(raw).unwrap() {
+            (json, EitherStatusResponse::Normal(response)) => (json, response),
+            (_, EitherStatusResponse::Text { text }) => panic!("expected a status, got {}", text),
+        };
+
+        let summary = summarize(&response, &raw_json, "A server".to_string(), &[]);
+
+        assert_eq!(summary.version, "Paper 1.19.4");
+        assert_eq!(summary.protocol, 762);
+        assert!(summary.suspicious());
+        assert_eq!(summary.players.len(), 1);
+        assert_eq!(summary.players[0].0, "Notch");
+        assert_eq!(
+            summary.favicon.as_deref(),
+            Some("data:image/png;base64,iVBORw0K")
+        );
+        assert_eq!(summary.secure_chat, Some(true));
+        assert_eq!(summary.chat_preview, None);
+        assert!(!summary.modded());
+        assert_eq!(JsonStatus::new(&summary).players, ["Notch"]);
+    }
+
+    /// the summary of a status with the given player counts
+    fn summary_with_counts(online: i64, max: i64) -> StatusSummary {
+        let raw = format!(
+            r#"{{
+                "version": {{ "name": "1.19.4", "protocol": 762 }},
+                "players": {{ "online": {}, "max": {} }},
+                "description": {{ "text": "" }}
+            }}"#,
+            online, max
+        );
+        match parse_status(&raw).unwrap() {
+            (json, EitherStatusResponse::Normal(response)) => {
+                summarize(&response, &json, String::new(), &[])
+            },
+            (_, EitherStatusResponse::Text { text }) => panic!("expected a status, got {}", text),
+        }
+    }
+
+    #[test]
+    fn flags_more_players_than_allowed() {
+        assert!(summary_with_counts(30, 20).suspicious());
+        assert!(!summary_with_counts(20, 20).suspicious());
+    }
+
+    #[test]
+    fn flags_negative_player_counts() {
+        let summary = summary_with_counts(-5, 20);
+        assert!(summary.suspicious());
+        assert_eq!(summary.online, 0);
+        assert_eq!(summary.reported_online, -5);
+
+        let summary = summary_with_counts(3, -1);
+        assert!(summary.negative_count());
+        assert_eq!(summary.reported_max, -1);
+    }
+}
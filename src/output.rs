@@ -1,17 +1,42 @@
+use crate::{
+    html_escape,
+    mc_formatted_to_html,
+    mc_formatted_width,
+    strip_control_chars,
+    strip_mc_formatting,
+};
 use crossterm::{
     style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor},
     ExecutableCommand,
 };
+use smart_default::SmartDefault;
 use std::{
-    cmp::max,
+    cmp::{max, Ordering},
     io::{self, Write},
+    time::Duration,
 };
-use unicode_width::UnicodeWidthStr;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-#[derive(Default)]
+#[derive(SmartDefault)]
 pub struct Table {
     pub entries: Vec<Box<dyn TableEntry>>,
     pub small_entry_width: usize,
+    /// if false, contents are printed without any styling
+    #[default(true)]
+    pub color: bool,
+    /// if set, the contents of big entries are word-wrapped to this width
+    pub max_width: Option<usize>,
+    /// if true, the names of small entries are highlighted. Has no effect if
+    /// `color` is false.
+    pub color_keys: bool,
+    /// if true, a thin line is drawn between consecutive small entries. Has no
+    /// effect if `color` is false.
+    pub dividers: bool,
+    /// if set, the values of small entries are cut off at this width
+    pub max_value_width: Option<usize>,
+    /// the character the borders of big entries are drawn with
+    #[default('=')]
+    pub big_border_char: char,
 }
 
 impl Table {
@@ -20,8 +45,40 @@ impl Table {
     }
 
     pub fn print(&self, out: &mut dyn Write) -> io::Result<()> {
+        let mut previous_small = false;
         for e in &self.entries {
+            let small = e.is_small();
+            if self.dividers && self.color && previous_small && small {
+                // aligned with the ` | ` between the name and the value
+                writeln!(out, "{}┈┼┈", "┈".repeat(self.small_entry_width))?;
+            }
+
             e.print(out, self)?;
+            previous_small = small;
+        }
+
+        Ok(())
+    }
+
+    /// prints the table as a HTML `<table>`. Formatting codes are converted to
+    /// inline styles.
+    pub fn print_html(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "<table>")?;
+        for e in &self.entries {
+            e.print_html(out)?;
+        }
+        writeln!(out, "</table>")?;
+
+        Ok(())
+    }
+
+    /// prints the table as a GitHub-flavored markdown table. Formatting codes
+    /// are stripped, since markdown can't display them.
+    pub fn print_markdown(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "| Key | Value |")?;
+        writeln!(out, "| --- | --- |")?;
+        for e in &self.entries {
+            e.print_markdown(out)?;
         }
 
         Ok(())
@@ -31,6 +88,10 @@ impl Table {
         Default::default()
     }
 
+    pub fn builder() -> TableBuilder {
+        TableBuilder::default()
+    }
+
     pub fn blank(&mut self) {
         self.entries.push(Box::new(BlankTableEntry));
     }
@@ -53,11 +114,152 @@ impl Table {
             self.small_entry_width = width;
         }
     }
+
+    /// writes `content` to `out`, without styling if color is disabled
+    fn write_content(&self, content: &dyn TableContent, out: &mut dyn Write) -> io::Result<()> {
+        if self.color {
+            content.write_to(out)
+        } else {
+            content.write_plain(out)
+        }
+    }
+}
+
+/// a builder for `Table`, for adding entries in a single expression:
+/// `Table::builder().small("Ping", "23").blank().build()`
+#[derive(Default)]
+pub struct TableBuilder(Table);
+
+impl TableBuilder {
+    pub fn small(mut self, name: impl ToString, val: impl TableContent + 'static) -> Self {
+        self.0.small_entry(name, val);
+        self
+    }
+
+    pub fn big(mut self, name: impl ToString, val: impl TableContent + 'static) -> Self {
+        self.0.big_entry(name, val);
+        self
+    }
+
+    pub fn blank(mut self) -> Self {
+        self.0.blank();
+        self
+    }
+
+    pub fn color(mut self, color: bool) -> Self {
+        self.0.color = color;
+        self
+    }
+
+    pub fn color_keys(mut self, color_keys: bool) -> Self {
+        self.0.color_keys = color_keys;
+        self
+    }
+
+    pub fn big_border_char(mut self, big_border_char: char) -> Self {
+        self.0.big_border_char = big_border_char;
+        self
+    }
+
+    pub fn dividers(mut self, dividers: bool) -> Self {
+        self.0.dividers = dividers;
+        self
+    }
+
+    pub fn max_value_width(mut self, max_value_width: Option<usize>) -> Self {
+        self.0.max_value_width = max_value_width;
+        self
+    }
+
+    pub fn max_width(mut self, max_width: Option<usize>) -> Self {
+        self.0.max_width = max_width;
+        self
+    }
+
+    pub fn build(self) -> Table {
+        self.0
+    }
+}
+
+/// a table with a single line per row, with each column padded to the width
+/// of its widest cell
+#[derive(Default)]
+pub struct CompactTable {
+    rows: Vec<Vec<String>>,
+    widths: Vec<usize>,
+}
+
+impl CompactTable {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn row(&mut self, cells: Vec<String>) {
+        // cells usually contain text sent by the server
+        let cells = cells
+            .into_iter()
+            .map(|cell| strip_control_chars(&cell).into_owned())
+            .collect::<Vec<_>>();
+        for (i, cell) in cells.iter().enumerate() {
+            match self.widths.get_mut(i) {
+                Some(w) => *w = max(*w, cell.width()),
+                None => self.widths.push(cell.width()),
+            }
+        }
+
+        self.rows.push(cells);
+    }
+
+    pub fn stdout(&self) -> io::Result<()> {
+        self.print(&mut io::stdout())
+    }
+
+    pub fn print(&self, out: &mut dyn Write) -> io::Result<()> {
+        for row in &self.rows {
+            let mut line = String::new();
+            for (i, (cell, width)) in row.iter().zip(&self.widths).enumerate() {
+                if i > 0 {
+                    line.push_str("  ");
+                }
+
+                // padded manually, since format widths count chars, not columns
+                line.push_str(cell);
+                line.push_str(&" ".repeat(width - cell.width()));
+            }
+            writeln!(out, "{}", line.trim_end())?;
+        }
+
+        Ok(())
+    }
 }
 
 pub trait TableContent {
     fn width(&self) -> usize;
     fn write_to(&self, out: &mut dyn Write) -> io::Result<()>;
+
+    /// writes the content without any styling
+    fn write_plain(&self, out: &mut dyn Write) -> io::Result<()> {
+        self.write_to(out)
+    }
+
+    /// writes the content as escaped HTML
+    fn write_html(&self, out: &mut dyn Write) -> io::Result<()> {
+        let mut buf = vec![];
+        self.write_plain(&mut buf)?;
+        out.write_all(html_escape(&String::from_utf8_lossy(&buf)).as_bytes())
+    }
+
+    /// returns the content word-wrapped to `width` columns, or `None` if this
+    /// content can't be wrapped
+    fn wrapped(&self, _width: usize) -> Option<Box<dyn TableContent>> {
+        None
+    }
+
+    /// returns the content with each line cut off at `width` columns, or
+    /// `None` if this content can't be truncated
+    fn truncated(&self, _width: usize) -> Option<Box<dyn TableContent>> {
+        None
+    }
 }
 
 impl TableContent for String {
@@ -65,9 +267,159 @@ impl TableContent for String {
         self.lines().map(|s| s.width()).max().unwrap_or_default()
     }
 
+    // values like the server version are sent by the server, so they may not
+    // contain escape sequences
+    fn write_to(&self, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(strip_control_chars(self).as_bytes())
+    }
+
+    fn wrapped(&self, width: usize) -> Option<Box<dyn TableContent>> {
+        Some(Box::new(wrap_lines(self, width, |w| w.width())))
+    }
+
+    fn truncated(&self, width: usize) -> Option<Box<dyn TableContent>> {
+        Some(Box::new(truncate_lines(self, width, false)))
+    }
+}
+
+impl TableContent for &'static str {
+    fn width(&self) -> usize {
+        self.lines().map(|s| s.width()).max().unwrap_or_default()
+    }
+
     fn write_to(&self, out: &mut dyn Write) -> io::Result<()> {
         out.write_all(self.as_bytes())
     }
+
+    fn wrapped(&self, width: usize) -> Option<Box<dyn TableContent>> {
+        Some(Box::new(wrap_lines(self, width, |w| w.width())))
+    }
+
+    fn truncated(&self, width: usize) -> Option<Box<dyn TableContent>> {
+        Some(Box::new(truncate_lines(self, width, false)))
+    }
+}
+
+/// Table content printed in a single color
+pub struct ColoredValue {
+    pub text: String,
+    pub color: Color,
+}
+
+impl ColoredValue {
+    pub fn new(text: String, color: Color) -> Self {
+        Self { text, color }
+    }
+}
+
+impl TableContent for ColoredValue {
+    fn width(&self) -> usize {
+        self.text.width()
+    }
+
+    fn write_to(&self, out: &mut dyn Write) -> io::Result<()> {
+        out.execute(SetForegroundColor(self.color))?;
+        out.write_all(strip_control_chars(&self.text).as_bytes())?;
+        out.execute(ResetColor)?;
+        Ok(())
+    }
+
+    fn write_plain(&self, out: &mut dyn Write) -> io::Result<()> {
+        self.text.write_to(out)
+    }
+
+    fn truncated(&self, width: usize) -> Option<Box<dyn TableContent>> {
+        Some(Box::new(Self::new(
+            truncate_lines(&self.text, width, false),
+            self.color,
+        )))
+    }
+}
+
+/// Table content of a value followed by how much it changed, if it did
+pub struct DeltaValue {
+    pub text: String,
+    pub delta: i64,
+}
+
+impl DeltaValue {
+    pub fn new(text: String, delta: i64) -> Self {
+        Self { text, delta }
+    }
+
+    fn delta_text(&self) -> Option<String> {
+        match self.delta.cmp(&0) {
+            Ordering::Greater => Some(format!("▲+{}", self.delta)),
+            Ordering::Less => Some(format!("▼{}", self.delta)),
+            Ordering::Equal => None,
+        }
+    }
+}
+
+impl TableContent for DeltaValue {
+    fn width(&self) -> usize {
+        self.text.width() + self.delta_text().map_or(0, |d| d.width() + 1)
+    }
+
+    fn write_to(&self, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(self.text.as_bytes())?;
+        if let Some(delta) = self.delta_text() {
+            let color = if self.delta > 0 {
+                Color::Green
+            } else {
+                Color::Red
+            };
+            out.write_all(b" ")?;
+            ColoredValue::new(delta, color).write_to(out)?;
+        }
+        Ok(())
+    }
+
+    fn write_plain(&self, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(self.text.as_bytes())?;
+        if let Some(delta) = self.delta_text() {
+            write!(out, " {}", delta)?;
+        }
+        Ok(())
+    }
+}
+
+/// Table content of a list whose first line is a header row, which is
+/// underlined. The rest is printed as-is, without applying formatting codes.
+pub struct HeaderedContent(pub String);
+
+impl TableContent for HeaderedContent {
+    fn width(&self) -> usize {
+        TableContent::width(&self.0)
+    }
+
+    fn write_to(&self, out: &mut dyn Write) -> io::Result<()> {
+        let (header, body) = match self.0.split_once('\n') {
+            Some((header, body)) => (header, Some(body)),
+            None => (&*self.0, None),
+        };
+
+        out.execute(SetAttribute(Attribute::Underlined))?;
+        out.write_all(strip_control_chars(header).as_bytes())?;
+        out.execute(SetAttribute(Attribute::NoUnderline))?;
+        if let Some(body) = body {
+            out.write_all(b"\n")?;
+            out.write_all(strip_control_chars(body).as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn write_plain(&self, out: &mut dyn Write) -> io::Result<()> {
+        self.0.write_to(out)
+    }
+
+    fn wrapped(&self, width: usize) -> Option<Box<dyn TableContent>> {
+        Some(Box::new(Self(wrap_lines(&self.0, width, |w| w.width()))))
+    }
+
+    fn truncated(&self, width: usize) -> Option<Box<dyn TableContent>> {
+        Some(Box::new(Self(truncate_lines(&self.0, width, false))))
+    }
 }
 
 /// Table content of a pretty string with minecraft-formatted markup
@@ -84,10 +436,7 @@ impl TableContent for McFormatContent {
     fn width(&self) -> usize {
         self.0
             .lines()
-            .map(|l| {
-                // need to count chars because of § being 2 bytes
-                l.chars().count() - l.matches('§').count() * 2
-            })
+            .map(mc_formatted_width)
             .max()
             .unwrap_or_default()
     }
@@ -107,16 +456,35 @@ impl TableContent for McFormatContent {
             }};
         }
 
-        let mut splits = self.0.split('§');
+        // only our own escape sequences should reach the terminal
+        let text = strip_control_chars(&self.0);
+        let mut splits = text.split('§');
         if let Some(n) = splits.next() {
             exec!(Print(n));
         }
 
         let mut empty = true;
-        for split in splits {
+        while let Some(split) = splits.next() {
             empty = false;
             if let Some(c) = split.chars().next() {
                 match c {
+                    // Hex colors, sent as §x§R§R§G§G§B§B
+                    'x' => {
+                        match parse_hex_color(&mut splits) {
+                            Some((color, rest)) => {
+                                exec!(SetForegroundColor(color));
+                                exec!(Print(rest));
+                            },
+                            // malformed sequence, print it as-is
+                            None => exec!(Print(format!(
+                                "§{}{}",
+                                split,
+                                malformed_hex_digits(&mut splits)
+                            ))),
+                        }
+                        continue;
+                    },
+
                     // Colors
                     '0' => exec!(fg, Black),
                     '1' => exec!(fg, DarkBlue),
@@ -142,41 +510,294 @@ impl TableContent for McFormatContent {
                     'm' => exec!(at, CrossedOut),
                     'n' => exec!(at, Underlined),
                     'o' => exec!(at, Italic),
-                    'r' => exec!(ResetColor),
+                    // resets formatting as well as the color
+                    'r' => exec!(at, Reset),
                     _ => {},
                 }
-                exec!(Print(&split[1..]));
+                exec!(Print(&split[c.len_utf8()..]));
             }
         }
 
-        // no need to reset color if there were no escape codes.
+        // no need to reset if there were no escape codes. Formatting is reset
+        // too, so it doesn't leak into the rest of the table.
         if !empty {
-            exec!(ResetColor);
+            exec!(at, Reset);
         }
 
         Ok(())
     }
+
+    fn write_plain(&self, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(strip_mc_formatting(&strip_control_chars(&self.0)).as_bytes())
+    }
+
+    fn write_html(&self, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(mc_formatted_to_html(&self.0).as_bytes())
+    }
+
+    fn wrapped(&self, width: usize) -> Option<Box<dyn TableContent>> {
+        // formatting codes never contain spaces, so they are kept intact
+        Some(Box::new(McFormatContent(wrap_lines(
+            &self.0,
+            width,
+            mc_formatted_width,
+        ))))
+    }
+
+    fn truncated(&self, width: usize) -> Option<Box<dyn TableContent>> {
+        Some(Box::new(McFormatContent(truncate_lines(
+            &self.0, width, true,
+        ))))
+    }
+}
+
+/// word-wraps each line of `s` to `width` columns, measuring words with
+/// `word_width`. Words that are wider than `width` are not split.
+fn wrap_lines(s: &str, width: usize, word_width: impl Fn(&str) -> usize) -> String {
+    let mut out = String::with_capacity(s.len());
+    for (i, line) in s.lines().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+
+        let mut line_width = 0;
+        for (j, word) in line.split(' ').enumerate() {
+            let w = word_width(word);
+            if j > 0 {
+                if line_width > 0 && line_width + 1 + w > width {
+                    out.push('\n');
+                    line_width = 0;
+                } else {
+                    out.push(' ');
+                    line_width += 1;
+                }
+            }
+
+            out.push_str(word);
+            line_width += w;
+        }
+    }
+    out
+}
+
+/// cuts off each line of `s` that is wider than `width` columns, ending it with
+/// `…`. If possible, lines are cut between words. If `formatted` is set, `§`
+/// formatting codes are kept intact and don't count towards the width.
+fn truncate_lines(s: &str, width: usize, formatted: bool) -> String {
+    let line_width = |line: &str| {
+        if formatted {
+            mc_formatted_width(line)
+        } else {
+            line.width()
+        }
+    };
+
+    let mut out = String::with_capacity(s.len());
+    for (i, line) in s.lines().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+
+        if line_width(line) <= width {
+            out.push_str(line);
+            continue;
+        }
+
+        // the longest start of the line that leaves space for the ellipsis
+        let mut end = line.len();
+        let mut used = 0;
+        let mut chars = line.char_indices();
+        while let Some((idx, c)) = chars.next() {
+            if formatted && c == '§' {
+                chars.next();
+                continue;
+            }
+
+            let w = c.width().unwrap_or_default();
+            if used + w + 1 > width {
+                end = idx;
+                break;
+            }
+            used += w;
+        }
+
+        let start = &line[..end];
+        let start = match start.rfind(' ') {
+            // don't cut between words if that would remove most of the text
+            Some(space) if space >= start.len() / 2 => &start[..space],
+            _ => start,
+        };
+        out.push_str(start.trim_end());
+        out.push('…');
+    }
+    out
+}
+
+/// draws the pings as a line of block characters, scaled so the highest ping
+/// is a full block
+pub fn sparkline(pings: &[Duration]) -> String {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    let highest = pings.iter().max().copied().unwrap_or_default();
+    pings
+        .iter()
+        .map(|ping| {
+            if highest.is_zero() {
+                return BLOCKS[0];
+            }
+            let level = ping.as_secs_f64() / highest.as_secs_f64() * (BLOCKS.len() - 1) as f64;
+            BLOCKS[level.round() as usize]
+        })
+        .collect()
+}
+
+/// parses the 6 digits of a `§x§R§R§G§G§B§B` hex color from the splits
+/// following the `x` marker.
+///
+/// returns the color and the text following the last digit. `splits` is only
+/// advanced if the sequence is valid.
+pub(crate) fn parse_hex_color<'a>(
+    splits: &mut (impl Iterator<Item = &'a str> + Clone),
+) -> Option<(Color, &'a str)> {
+    let mut lookahead = splits.clone();
+    let mut rgb = 0u32;
+    let mut rest = "";
+    for i in 0..6 {
+        let mut chars = lookahead.next()?.chars();
+        rgb = (rgb << 4) | chars.next()?.to_digit(16)?;
+        rest = chars.as_str();
+
+        // only the last digit may be followed by text
+        if i < 5 && !rest.is_empty() {
+            return None;
+        }
+    }
+
+    *splits = lookahead;
+    Some((
+        Color::Rgb {
+            r: (rgb >> 16) as u8,
+            g: (rgb >> 8) as u8,
+            b: rgb as u8,
+        },
+        rest,
+    ))
+}
+
+/// consumes the digits of a malformed hex color from the splits following the
+/// `x` marker, returning them as they were sent, like `§f§f`. A digit followed
+/// by text ends the sequence.
+pub(crate) fn malformed_hex_digits<'a>(
+    splits: &mut (impl Iterator<Item = &'a str> + Clone),
+) -> String {
+    let mut digits = String::new();
+    for _ in 0..6 {
+        let mut lookahead = splits.clone();
+        match lookahead.next() {
+            Some(split) if split.starts_with(|c: char| c.is_ascii_hexdigit()) => {
+                *splits = lookahead;
+                digits.push('§');
+                digits.push_str(split);
+                if split.len() > 1 {
+                    break;
+                }
+            },
+            _ => break,
+        }
+    }
+    digits
 }
 
 pub trait TableEntry {
     fn print(&self, out: &mut dyn Write, table: &Table) -> io::Result<()>;
+
+    /// whether this is a single-line entry, which may be separated by dividers
+    fn is_small(&self) -> bool {
+        false
+    }
+
+    /// prints the entry as a row of a markdown table. Entries without a value
+    /// print nothing.
+    fn print_markdown(&self, _out: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// prints the entry as a row of a HTML table. Entries without a value
+    /// print nothing.
+    fn print_html(&self, _out: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// writes a row of a HTML table
+fn write_html_row(out: &mut dyn Write, name: &str, val: &dyn TableContent) -> io::Result<()> {
+    let mut buf = vec![];
+    val.write_html(&mut buf)?;
+
+    writeln!(
+        out,
+        "<tr><th>{}</th><td>{}</td></tr>",
+        html_escape(name),
+        String::from_utf8_lossy(&buf)
+            .trim_end()
+            .replace('\n', "<br>")
+    )
+}
+
+/// writes a row of a markdown table, escaping the cells
+fn write_markdown_row(out: &mut dyn Write, name: &str, val: &dyn TableContent) -> io::Result<()> {
+    let mut buf = vec![];
+    val.write_plain(&mut buf)?;
+
+    let escape = |s: &str| s.trim_end().replace('|', "\\|").replace('\n', "<br>");
+    writeln!(
+        out,
+        "| {} | {} |",
+        escape(name),
+        escape(&String::from_utf8_lossy(&buf))
+    )
 }
 
 pub struct SmallTableEntry(String, Box<dyn TableContent>);
 
 impl TableEntry for SmallTableEntry {
     fn print(&self, out: &mut dyn Write, table: &Table) -> io::Result<()> {
-        write!(
-            out,
-            "{: <width$} | ",
-            self.0,
-            width = table.small_entry_width
-        )?;
-        self.1.write_to(out)?;
+        if table.color && table.color_keys {
+            out.execute(SetAttribute(Attribute::Bold))?;
+            out.execute(SetForegroundColor(Color::Cyan))?;
+            write!(out, "{: <width$}", self.0, width = table.small_entry_width)?;
+            out.execute(ResetColor)?;
+            out.execute(SetAttribute(Attribute::NormalIntensity))?;
+            out.write_all(b" | ")?;
+        } else {
+            write!(
+                out,
+                "{: <width$} | ",
+                self.0,
+                width = table.small_entry_width
+            )?;
+        }
+        let truncated = table
+            .max_value_width
+            .filter(|&w| self.1.width() > w)
+            .and_then(|w| self.1.truncated(w));
+        table.write_content(truncated.as_deref().unwrap_or(&*self.1), out)?;
         out.write_all(b"\n")?;
 
         Ok(())
     }
+
+    fn is_small(&self) -> bool {
+        true
+    }
+
+    fn print_markdown(&self, out: &mut dyn Write) -> io::Result<()> {
+        write_markdown_row(out, &self.0, &*self.1)
+    }
+
+    fn print_html(&self, out: &mut dyn Write) -> io::Result<()> {
+        write_html_row(out, &self.0, &*self.1)
+    }
 }
 
 pub struct BigTableEntry {
@@ -185,15 +806,43 @@ pub struct BigTableEntry {
 }
 
 impl TableEntry for BigTableEntry {
-    fn print(&self, out: &mut dyn Write, _table: &Table) -> io::Result<()> {
-        let width = max(self.val.width(), self.name.width() + 4);
-
-        writeln!(out, "{:=^width$}", self.name)?;
-        self.val.write_to(out)?;
-        writeln!(out, "\n{:=<width$}", "")?;
+    fn print(&self, out: &mut dyn Write, table: &Table) -> io::Result<()> {
+        let wrapped = table.max_width.and_then(|w| self.val.wrapped(w));
+        let val = wrapped.as_deref().unwrap_or(&*self.val);
+        // wide characters like CJK take up two columns each, so the width is
+        // rounded up to fit a whole number of them
+        let char_width = table.big_border_char.width().unwrap_or(1).max(1);
+        let width = max(val.width(), self.name.width() + 4);
+        let width = (width + char_width - 1) / char_width * char_width;
+
+        // the columns that don't fit a whole character are filled with spaces
+        // next to the name
+        let fill = |n: usize| table.big_border_char.to_string().repeat(n / char_width);
+        let space = |n: usize| " ".repeat(n % char_width);
+        let padding = width.saturating_sub(self.name.width());
+        let (left, right) = (padding / 2, padding - padding / 2);
+        writeln!(
+            out,
+            "{}{}{}{}{}",
+            fill(left),
+            space(left),
+            self.name,
+            space(right),
+            fill(right)
+        )?;
+        table.write_content(val, out)?;
+        writeln!(out, "\n{}", fill(width))?;
 
         Ok(())
     }
+
+    fn print_markdown(&self, out: &mut dyn Write) -> io::Result<()> {
+        write_markdown_row(out, &self.name, &*self.val)
+    }
+
+    fn print_html(&self, out: &mut dyn Write) -> io::Result<()> {
+        write_html_row(out, &self.name, &*self.val)
+    }
 }
 
 impl BigTableEntry {
@@ -212,3 +861,137 @@ impl TableEntry for BlankTableEntry {
         out.write(b"\n").map(|_| ())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the bytes written by `content`
+    fn written(content: &dyn TableContent) -> Vec<u8> {
+        let mut out = vec![];
+        content.write_to(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn escape_sequences_are_neutralized() {
+        let text = "Paper\x1b[2J 1.19";
+        assert_eq!(written(&text.to_string()), b"Paper[2J 1.19");
+        assert_eq!(
+            written(&McFormatContent(text.to_string())),
+            b"Paper[2J 1.19"
+        );
+
+        let colored = written(&ColoredValue::new(text.to_string(), Color::Red));
+        assert!(!String::from_utf8(colored).unwrap().contains("\x1b[2J"));
+    }
+
+    #[test]
+    fn only_the_header_of_lists_is_styled() {
+        let list = "Mod ID | Version\n§kforge  | §c1.0";
+        let out = written(&HeaderedContent(list.to_string()));
+
+        assert!(out.starts_with(b"\x1b[4mMod ID | Version\x1b[24m\n"));
+        assert!(out.ends_with("\n§kforge  | §c1.0".as_bytes()));
+    }
+
+    /// the text printed by `table`, without styling
+    fn printed(table: &Table) -> String {
+        let mut out = vec![];
+        table.print(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn big_entry_border() {
+        let table = Table::builder()
+            .color(false)
+            .big_border_char('─')
+            .big("Mods", "jei\ncreate 0.5")
+            .build();
+
+        assert_eq!(printed(&table), "───Mods───\njei\ncreate 0.5\n──────────\n");
+    }
+
+    #[test]
+    fn big_entry_wide_border() {
+        let table = Table::builder()
+            .color(false)
+            .big_border_char('字')
+            .big("Mods", "create 0.5")
+            .build();
+        let printed = printed(&table);
+        let lines = printed.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines[0], "字 Mods 字");
+        assert_eq!(lines[2], "字字字字字");
+        assert_eq!(lines[0].width(), lines[2].width());
+    }
+
+    #[test]
+    fn hex_gradient_escape_codes() {
+        let gradient = McFormatContent("§x§f§f§0§0§0§0R§x§0§0§f§f§0§0G".to_string());
+        assert_eq!(
+            written(&gradient),
+            b"\x1b[38;2;255;0;0mR\x1b[38;2;0;255;0mG\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn reset_escape_codes() {
+        let text = McFormatContent("§cRed§r plain".to_string());
+        assert_eq!(written(&text), b"\x1b[38;5;9mRed\x1b[0m plain\x1b[0m");
+    }
+
+    #[test]
+    fn truncated_hex_color_is_printed_literally() {
+        let text = McFormatContent("§x§f§fText".to_string());
+        assert_eq!(written(&text), "§x§f§fText\x1b[0m".as_bytes());
+        assert_eq!(text.width(), "§x§f§fText".width());
+
+        // the digits aren't applied as legacy colors
+        let text = McFormatContent("§x§f§f§a".to_string());
+        assert_eq!(written(&text), "§x§f§f§a\x1b[0m".as_bytes());
+        assert_eq!(text.width(), 8);
+    }
+
+    /// `bytes` without the CSI escape sequences crossterm writes
+    fn without_escapes(bytes: &[u8]) -> String {
+        let text = String::from_utf8(bytes.to_vec()).unwrap();
+        let mut out = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                // `[`, the parameters and the final letter
+                chars.find(|c| c.is_ascii_alphabetic());
+            } else {
+                out.push(c);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn formatted_width_matches_output() {
+        let texts = [
+            "plain text",
+            "§aGreen §lbold§r text",
+            "§x§f§f§8§8§0§0Orange",
+            "§x§f§fTruncated",
+            "§x§f§f§a",
+            "Server§",
+            "§6我的世界§r服务器",
+            "§zunknown code",
+        ];
+        for text in texts {
+            let printed = without_escapes(&written(&McFormatContent(text.to_string())));
+            assert_eq!(
+                mc_formatted_width(text),
+                printed.width(),
+                "{:?} is printed as {:?}",
+                text,
+                printed
+            );
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! mapping of protocol numbers to the release versions of minecraft: java
+//! edition using them
+
+/// protocol numbers and the releases using them, sorted by protocol number.
+/// Snapshots are not included.
+const VERSIONS: &[(u32, &str)] = &[
+    (4, "1.7.2-1.7.5"),
+    (5, "1.7.6-1.7.10"),
+    (47, "1.8-1.8.9"),
+    (107, "1.9"),
+    (108, "1.9.1"),
+    (109, "1.9.2"),
+    (110, "1.9.3-1.9.4"),
+    (210, "1.10-1.10.2"),
+    (315, "1.11"),
+    (316, "1.11.1-1.11.2"),
+    (335, "1.12"),
+    (338, "1.12.1"),
+    (340, "1.12.2"),
+    (393, "1.13"),
+    (401, "1.13.1"),
+    (404, "1.13.2"),
+    (477, "1.14"),
+    (480, "1.14.1"),
+    (485, "1.14.2"),
+    (490, "1.14.3"),
+    (498, "1.14.4"),
+    (573, "1.15"),
+    (575, "1.15.1"),
+    (578, "1.15.2"),
+    (735, "1.16"),
+    (736, "1.16.1"),
+    (751, "1.16.2"),
+    (753, "1.16.3"),
+    (754, "1.16.4-1.16.5"),
+    (755, "1.17"),
+    (756, "1.17.1"),
+    (757, "1.18-1.18.1"),
+    (758, "1.18.2"),
+    (759, "1.19"),
+    (760, "1.19.1-1.19.2"),
+    (761, "1.19.3"),
+    (762, "1.19.4"),
+    (763, "1.20-1.20.1"),
+    (764, "1.20.2"),
+    (765, "1.20.3-1.20.4"),
+    (766, "1.20.5-1.20.6"),
+    (767, "1.21-1.21.1"),
+    (768, "1.21.2-1.21.3"),
+    (769, "1.21.4"),
+    (770, "1.21.5"),
+    (771, "1.21.6"),
+    (772, "1.21.7-1.21.8"),
+];
+
+/// returns the release versions using the given protocol number, if it is
+/// known
+pub fn version_name(protocol: u32) -> Option<&'static str> {
+    VERSIONS
+        .binary_search_by_key(&protocol, |&(p, _)| p)
+        .ok()
+        .map(|i| VERSIONS[i].1)
+}
+
+/// returns the protocol number of the release version contained in a version
+/// name like `Paper 1.19.4`, if it is known
+pub fn protocol_of(name: &str) -> Option<u32> {
+    let version = name
+        .split(|c: char| !c.is_ascii_digit() && c != '.')
+        .filter(|s| s.contains('.'))
+        .find_map(parse_version)?;
+
+    VERSIONS.iter().find_map(|&(protocol, releases)| {
+        let (first, last) = releases.split_once('-').unwrap_or((releases, releases));
+        let range = parse_version(first)?..=parse_version(last)?;
+        range.contains(&version).then(|| protocol)
+    })
+}
+
+/// parses a version like `1.19` or `1.19.4` into its major, minor and patch
+/// numbers
+fn parse_version(s: &str) -> Option<[u32; 3]> {
+    let mut parts = s.trim_matches('.').split('.').map(str::parse);
+    let major = parts.next()?.ok()?;
+    let minor = parts.next()?.ok()?;
+    let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+    Some([major, minor, patch])
+}
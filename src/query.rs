@@ -0,0 +1,209 @@
+//! Client for the UDP query protocol, which is available if `enable-query` is
+//! set in the server's `server.properties`. It provides more information than
+//! the status request, like the full player list and plugins.
+
+use crate::connect_udp;
+use miette::{bail, miette, IntoDiagnostic, WrapErr};
+use std::{collections::HashMap, net::IpAddr};
+use tokio::net::UdpSocket;
+use tracing::info;
+
+/// the session ID sent with requests. Only the lower 4 bits of each byte are
+/// used by the server.
+const SESSION_ID: i32 = 0x0102_0304;
+
+/// response to a full stat request
+pub struct FullStat {
+    /// key-value section of the response, containing keys like `hostname`,
+    /// `gametype`, `version`, `plugins`, `map`, `numplayers` and
+    /// `maxplayers`
+    pub info: HashMap<String, String>,
+    /// names of all online players
+    pub players: Vec<String>,
+}
+
+impl FullStat {
+    pub fn map(&self) -> Option<&str> {
+        self.info.get("map").map(String::as_str)
+    }
+
+    pub fn game_type(&self) -> Option<&str> {
+        self.info.get("gametype").map(String::as_str)
+    }
+
+    /// the server software, if it sends one with its plugin list
+    pub fn server_mod(&self) -> Option<&str> {
+        self.info
+            .get("plugins")?
+            .split_once(':')
+            .map(|(server_mod, _)| server_mod.trim())
+    }
+
+    /// the plugins of the server. The plugin list has the format
+    /// `<server mod>: <plugin>; <plugin>`.
+    pub fn plugins(&self) -> Vec<&str> {
+        let plugins = match self.info.get("plugins") {
+            Some(p) => p,
+            None => return vec![],
+        };
+
+        let plugins = plugins
+            .split_once(':')
+            .map_or(plugins.as_str(), |(_, plugins)| plugins);
+
+        plugins
+            .split(';')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .collect()
+    }
+}
+
+/// performs a handshake with the server and requests the full stat
+pub async fn full_stat(addr: &str, port: u16, bind: Option<IpAddr>) -> miette::Result<FullStat> {
+    let socket = connect_udp(addr, port, bind).await?;
+    let mut buf = vec![0u8; 65536];
+
+    info!("Sending query handshake");
+    let token = request(&socket, &mut buf, 0x09, &[]).await?;
+    let token = String::from_utf8_lossy(token.split(|&b| b == 0).next().unwrap_or_default())
+        .parse::<i32>()
+        .into_diagnostic()
+        .wrap_err("Server sent invalid challenge token")?;
+
+    info!("Requesting full stat");
+    let mut payload = token.to_be_bytes().to_vec();
+    // padding to request the full stat instead of the basic one
+    payload.extend_from_slice(&[0; 4]);
+    let data = request(&socket, &mut buf, 0x00, &payload).await?;
+
+    parse_full_stat(data)
+}
+
+/// sends a request of the given type, returning the payload of the response
+async fn request<'a>(
+    socket: &UdpSocket,
+    buf: &'a mut [u8],
+    ty: u8,
+    payload: &[u8],
+) -> miette::Result<&'a [u8]> {
+    let mut packet = vec![0xfe, 0xfd, ty];
+    packet.extend_from_slice(&SESSION_ID.to_be_bytes());
+    packet.extend_from_slice(payload);
+
+    socket
+        .send(&packet)
+        .await
+        .into_diagnostic()
+        .wrap_err("Failed to send query request")?;
+
+    let len = socket
+        .recv(buf)
+        .await
+        .into_diagnostic()
+        .wrap_err("Failed to receive query response")?;
+
+    let data = &buf[..len];
+    if data.len() < 5 || data[0] != ty || data[1..5] != SESSION_ID.to_be_bytes() {
+        bail!("Server sent an invalid query response");
+    }
+
+    Ok(&data[5..])
+}
+
+fn parse_full_stat(data: &[u8]) -> miette::Result<FullStat> {
+    // the response starts with 11 bytes of padding
+    let data = data
+        .get(11..)
+        .ok_or_else(|| miette!("Full stat response is too short"))?;
+    let mut strings = data
+        .split(|&b| b == 0)
+        .map(|s| String::from_utf8_lossy(s).into_owned());
+
+    // key-value pairs, terminated by an empty key
+    let mut info = HashMap::new();
+    loop {
+        let key = strings
+            .next()
+            .ok_or_else(|| miette!("Full stat response is incomplete"))?;
+        if key.is_empty() {
+            break;
+        }
+
+        let value = strings
+            .next()
+            .ok_or_else(|| miette!("Full stat response is incomplete"))?;
+        info.insert(key, value);
+    }
+
+    // skip the "\x01player_\0" padding before the player list
+    strings.next();
+    strings.next();
+
+    let players = strings.take_while(|s| !s.is_empty()).collect();
+
+    Ok(FullStat { info, players })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a full stat response with the given key-value pairs and player section
+    fn full_stat(info: &[(&str, &str)], players: Option<&[&str]>) -> Vec<u8> {
+        let mut data = b"splitnum\0\x80\0".to_vec();
+        for (key, value) in info {
+            data.extend_from_slice(key.as_bytes());
+            data.push(0);
+            data.extend_from_slice(value.as_bytes());
+            data.push(0);
+        }
+        data.push(0);
+
+        if let Some(players) = players {
+            data.extend_from_slice(b"\x01player_\0\0");
+            for player in players {
+                data.extend_from_slice(player.as_bytes());
+                data.push(0);
+            }
+            data.push(0);
+        }
+        data
+    }
+
+    const INFO: &[(&str, &str)] = &[
+        ("hostname", "A Server"),
+        ("gametype", "SMP"),
+        ("map", "world"),
+        ("numplayers", "2"),
+        ("maxplayers", "20"),
+    ];
+
+    #[test]
+    fn parses_full_stat() {
+        let stat = parse_full_stat(&full_stat(INFO, Some(&["Notch", "jeb_"]))).unwrap();
+
+        assert_eq!(stat.info["hostname"], "A Server");
+        assert_eq!(stat.game_type(), Some("SMP"));
+        assert_eq!(stat.map(), Some("world"));
+        assert_eq!(stat.players, ["Notch", "jeb_"]);
+    }
+
+    #[test]
+    fn parses_full_stat_without_players() {
+        let stat = parse_full_stat(&full_stat(INFO, None)).unwrap();
+
+        assert_eq!(stat.map(), Some("world"));
+        assert!(stat.players.is_empty());
+    }
+
+    #[test]
+    fn rejects_truncated_full_stat() {
+        let data = full_stat(INFO, Some(&["Notch"]));
+
+        assert!(parse_full_stat(&data[..5]).is_err());
+        // cut off in the middle of the key-value section
+        assert!(parse_full_stat(&data[..30]).is_err());
+        assert!(parse_full_stat(&data[..11 + "hostname".len()]).is_err());
+    }
+}
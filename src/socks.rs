@@ -0,0 +1,173 @@
+//! Connecting to servers through a SOCKS5 proxy, optionally using username and
+//! password authentication.
+
+use miette::{bail, IntoDiagnostic, WrapErr};
+use std::net::IpAddr;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use tracing::info;
+
+const VERSION: u8 = 5;
+const NO_AUTH: u8 = 0;
+const USERNAME_PASSWORD: u8 = 2;
+const CONNECT: u8 = 1;
+
+/// a SOCKS5 proxy
+#[derive(Debug, Clone)]
+pub struct Proxy {
+    /// the address of the proxy in the format `host:port`
+    pub addr: String,
+    /// the username and password to authenticate with, if any
+    pub auth: Option<(String, String)>,
+}
+
+/// connects to the given address through the proxy, given a stream connected
+/// to the proxy. The stream is returned once the proxy is connected to the
+/// server.
+pub async fn connect(
+    mut stream: TcpStream,
+    proxy: &Proxy,
+    addr: &str,
+    port: u16,
+) -> miette::Result<TcpStream> {
+    let greeting = match proxy.auth {
+        Some(_) => vec![VERSION, 2, NO_AUTH, USERNAME_PASSWORD],
+        None => vec![VERSION, 1, NO_AUTH],
+    };
+    stream
+        .write_all(&greeting)
+        .await
+        .into_diagnostic()
+        .wrap_err("Failed to send greeting to proxy")?;
+
+    let mut reply = [0u8; 2];
+    stream
+        .read_exact(&mut reply)
+        .await
+        .into_diagnostic()
+        .wrap_err("Failed to read greeting of proxy")?;
+
+    if reply[0] != VERSION {
+        bail!("Proxy is not a SOCKS5 proxy");
+    }
+
+    match (reply[1], &proxy.auth) {
+        (NO_AUTH, _) => {},
+        (USERNAME_PASSWORD, Some((username, password))) => {
+            authenticate(&mut stream, username, password).await?
+        },
+        (USERNAME_PASSWORD, None) => bail!("Proxy requires authentication"),
+        _ => bail!("Proxy doesn't support any of the authentication methods"),
+    }
+
+    info!("Sending connect request to proxy");
+    let mut request = vec![VERSION, CONNECT, 0];
+    match addr.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(1);
+            request.extend_from_slice(&ip.octets());
+        },
+        Ok(IpAddr::V6(ip)) => {
+            request.push(4);
+            request.extend_from_slice(&ip.octets());
+        },
+        // domains are resolved by the proxy
+        Err(_) => {
+            if addr.len() > 255 {
+                bail!("Address is too long to be sent to the proxy");
+            }
+            request.push(3);
+            request.push(addr.len() as u8);
+            request.extend_from_slice(addr.as_bytes());
+        },
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+
+    stream
+        .write_all(&request)
+        .await
+        .into_diagnostic()
+        .wrap_err("Failed to send connect request to proxy")?;
+
+    let mut reply = [0u8; 4];
+    stream
+        .read_exact(&mut reply)
+        .await
+        .into_diagnostic()
+        .wrap_err("Failed to read connect response of proxy")?;
+
+    if reply[1] != 0 {
+        bail!(
+            "Proxy failed to connect to server: {}",
+            reply_message(reply[1])
+        );
+    }
+
+    // the address the proxy bound to is not needed
+    let addr_len = match reply[3] {
+        1 => 4,
+        4 => 16,
+        3 => stream.read_u8().await.into_diagnostic()? as usize,
+        ty => bail!("Proxy sent invalid address type {}", ty),
+    };
+    let mut bound = vec![0u8; addr_len + 2];
+    stream
+        .read_exact(&mut bound)
+        .await
+        .into_diagnostic()
+        .wrap_err("Failed to read connect response of proxy")?;
+
+    Ok(stream)
+}
+
+async fn authenticate(
+    stream: &mut TcpStream,
+    username: &str,
+    password: &str,
+) -> miette::Result<()> {
+    if username.len() > 255 || password.len() > 255 {
+        bail!("Proxy username and password must not be longer than 255 bytes");
+    }
+
+    info!("Authenticating to proxy");
+    let mut request = vec![1, username.len() as u8];
+    request.extend_from_slice(username.as_bytes());
+    request.push(password.len() as u8);
+    request.extend_from_slice(password.as_bytes());
+
+    stream
+        .write_all(&request)
+        .await
+        .into_diagnostic()
+        .wrap_err("Failed to authenticate to proxy")?;
+
+    let mut reply = [0u8; 2];
+    stream
+        .read_exact(&mut reply)
+        .await
+        .into_diagnostic()
+        .wrap_err("Failed to authenticate to proxy")?;
+
+    if reply[1] != 0 {
+        bail!("Proxy rejected the username or password");
+    }
+
+    Ok(())
+}
+
+/// describes the error code of a connect response
+fn reply_message(code: u8) -> &'static str {
+    match code {
+        1 => "general failure",
+        2 => "connection not allowed by ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error",
+    }
+}
@@ -0,0 +1,84 @@
+//! Wrapping connections in TLS, for servers behind proxies that terminate TLS
+//! before the minecraft protocol.
+
+use crate::TlsOptions;
+use miette::{miette, IntoDiagnostic, WrapErr};
+use std::{convert::TryFrom, sync::Arc, time::SystemTime};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::{
+    client::TlsStream,
+    rustls::{
+        client::{ServerCertVerified, ServerCertVerifier},
+        Certificate,
+        ClientConfig,
+        OwnedTrustAnchor,
+        RootCertStore,
+        ServerName,
+    },
+    TlsConnector,
+};
+use tracing::info;
+
+/// performs the TLS handshake on the given stream. The server name is taken
+/// from the options, or `addr` if none is set.
+pub async fn connect<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: S,
+    options: &TlsOptions,
+    addr: &str,
+) -> miette::Result<TlsStream<S>> {
+    let sni = options.sni.as_deref().unwrap_or(addr);
+    let name = ServerName::try_from(sni).map_err(|_| {
+        miette!(
+            "{} is not a valid TLS server name. Use --tls-sni to set one",
+            sni
+        )
+    })?;
+
+    info!("Performing TLS handshake with {}", sni);
+    TlsConnector::from(Arc::new(client_config(options.insecure)))
+        .connect(name, stream)
+        .await
+        .into_diagnostic()
+        .wrap_err("TLS handshake failed")
+}
+
+fn client_config(insecure: bool) -> ClientConfig {
+    let mut roots = RootCertStore::empty();
+    roots.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|ta| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+
+    let mut config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+    if insecure {
+        config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(NoVerification));
+    }
+
+    config
+}
+
+/// accepts any certificate, used with `--insecure`
+struct NoVerification;
+
+impl ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
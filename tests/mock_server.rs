@@ -0,0 +1,260 @@
+//! Runs status requests against a mock server listening on localhost, which
+//! answers with a canned status the way a minecraft server would.
+
+use mcstat::{fetch_raw_status, fetch_status, socks::Proxy, FetchOptions, StatusError};
+use std::net::SocketAddr;
+use tokio::{
+    io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+const STATUS: &str = r#"{
+    "version": { "name": "Mock 1.19.4", "protocol": 762 },
+    "players": {
+        "online": 3,
+        "max": 20,
+        "sample": [{ "name": "Notch", "id": "069a79f4-44e9-4726-a5be-fca90e38aaf5" }]
+    },
+    "description": { "text": "A mock server" }
+}"#;
+
+/// starts a mock server answering every connection with `status`, returning
+/// its address
+async fn mock_server(status: &'static str) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(handle_client(stream, addr.port(), status));
+        }
+    });
+
+    addr
+}
+
+async fn handle_client(mut stream: TcpStream, port: u16, status: &str) {
+    let (id, handshake) = read_packet(&mut stream).await.unwrap();
+    assert_eq!(id, 0x00, "first packet must be the handshake");
+
+    let mut data = &handshake[..];
+    read_varint(&mut data).await.unwrap();
+    let len = read_varint(&mut data).await.unwrap() as usize;
+    assert_eq!(&data[..len], b"127.0.0.1");
+    assert_eq!(data[len..len + 2], port.to_be_bytes());
+    assert_eq!(data[len + 2..], [1], "next state must be status");
+
+    // the client may send a status request, a ping or both
+    while let Ok((id, data)) = read_packet(&mut stream).await {
+        match id {
+            0x00 => {
+                assert!(data.is_empty(), "status request must be empty");
+                let mut response = varint(status.len() as i32);
+                response.extend_from_slice(status.as_bytes());
+                write_packet(&mut stream, 0x00, &response).await.unwrap();
+            },
+            0x01 => {
+                write_packet(&mut stream, 0x01, &data).await.unwrap();
+                return;
+            },
+            id => panic!("unexpected packet {:#x}", id),
+        }
+    }
+}
+
+/// starts a SOCKS5 proxy forwarding connections to their target, returning
+/// its address. If `auth` is set, clients must authenticate with it.
+async fn mock_proxy(auth: Option<(&'static str, &'static str)>) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(handle_proxy_client(stream, auth));
+        }
+    });
+
+    addr
+}
+
+async fn handle_proxy_client(mut stream: TcpStream, auth: Option<(&str, &str)>) {
+    let mut greeting = [0u8; 2];
+    stream.read_exact(&mut greeting).await.unwrap();
+    assert_eq!(greeting[0], 5);
+    let mut methods = vec![0u8; greeting[1] as usize];
+    stream.read_exact(&mut methods).await.unwrap();
+
+    match auth {
+        Some((username, password)) => {
+            assert!(methods.contains(&2), "client must offer password auth");
+            stream.write_all(&[5, 2]).await.unwrap();
+
+            assert_eq!(stream.read_u8().await.unwrap(), 1);
+            let len = stream.read_u8().await.unwrap() as usize;
+            let mut user = vec![0u8; len];
+            stream.read_exact(&mut user).await.unwrap();
+            let len = stream.read_u8().await.unwrap() as usize;
+            let mut pass = vec![0u8; len];
+            stream.read_exact(&mut pass).await.unwrap();
+
+            let ok = user == username.as_bytes() && pass == password.as_bytes();
+            stream.write_all(&[1, u8::from(!ok)]).await.unwrap();
+            if !ok {
+                return;
+            }
+        },
+        None => stream.write_all(&[5, 0]).await.unwrap(),
+    }
+
+    let mut request = [0u8; 4];
+    stream.read_exact(&mut request).await.unwrap();
+    assert_eq!(request[..3], [5, 1, 0]);
+    assert_eq!(request[3], 1, "IPv4 addresses are sent as such");
+    let mut ip = [0u8; 4];
+    stream.read_exact(&mut ip).await.unwrap();
+    let port = stream.read_u16().await.unwrap();
+
+    let mut target = TcpStream::connect((ip, port)).await.unwrap();
+    stream
+        .write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0])
+        .await
+        .unwrap();
+    let _ = io::copy_bidirectional(&mut stream, &mut target).await;
+}
+
+fn varint(value: i32) -> Vec<u8> {
+    let mut value = value as u32;
+    let mut buf = vec![];
+    loop {
+        if value & !0x7f == 0 {
+            buf.push(value as u8);
+            return buf;
+        }
+        buf.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+}
+
+async fn read_varint<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<i32> {
+    let mut value = 0u32;
+    for i in 0..5 {
+        let byte = stream.read_u8().await?;
+        value |= u32::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(value as i32);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "VarInt is too long",
+    ))
+}
+
+async fn read_packet<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<(i32, Vec<u8>)> {
+    let len = read_varint(stream).await? as usize;
+    let mut body = vec![0u8; len];
+    stream.read_exact(&mut body).await?;
+
+    let mut data = &body[..];
+    let id = read_varint(&mut data).await?;
+    Ok((id, data.to_vec()))
+}
+
+async fn write_packet<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    id: i32,
+    data: &[u8],
+) -> io::Result<()> {
+    let mut body = varint(id);
+    body.extend_from_slice(data);
+    let mut packet = varint(body.len() as i32);
+    packet.extend_from_slice(&body);
+    stream.write_all(&packet).await
+}
+
+#[tokio::test]
+async fn fetches_status() {
+    let addr = mock_server(STATUS).await;
+
+    let (response, pings) = fetch_status(&addr.to_string(), &FetchOptions::default())
+        .await
+        .unwrap();
+
+    assert_eq!(response.players.online, 3);
+    assert_eq!(response.players.max, 20);
+    assert_eq!(response.version.name, "Mock 1.19.4");
+    assert_eq!(response.version.protocol, 762);
+    assert_eq!(response.description.get_text(), "A mock server");
+    assert_eq!(pings.len(), 1);
+}
+
+#[tokio::test]
+async fn measures_every_ping_sample() {
+    let addr = mock_server(STATUS).await;
+    let options = FetchOptions {
+        ping_count: 3,
+        ..FetchOptions::default()
+    };
+
+    let raw = fetch_raw_status(&addr.to_string(), &options).await.unwrap();
+
+    assert_eq!(raw.pings.len(), 3);
+    assert!(raw.timing.is_some());
+    assert_eq!(raw.address.unwrap().port, addr.port());
+}
+
+#[tokio::test]
+async fn reports_server_message() {
+    let addr = mock_server(r#"{"text": "Under maintenance"}"#).await;
+
+    let error = fetch_status(&addr.to_string(), &FetchOptions::default())
+        .await
+        .unwrap_err();
+
+    match error.downcast_ref::<StatusError>() {
+        Some(StatusError::ServerMessage(text)) => assert_eq!(text, "Under maintenance"),
+        _ => panic!("expected a server message, got {:?}", error),
+    }
+}
+
+#[tokio::test]
+async fn fetches_status_through_proxy() {
+    let server = mock_server(STATUS).await;
+    let proxy = mock_proxy(None).await;
+    let options = FetchOptions {
+        proxy: Some(Proxy {
+            addr: proxy.to_string(),
+            auth: None,
+        }),
+        ..FetchOptions::default()
+    };
+
+    let (response, _) = fetch_status(&server.to_string(), &options).await.unwrap();
+
+    assert_eq!(response.players.online, 3);
+}
+
+#[tokio::test]
+async fn authenticates_to_proxy() {
+    let server = mock_server(STATUS).await;
+    let proxy = mock_proxy(Some(("steve", "hunter2"))).await;
+    let proxy_with = |password: &str| FetchOptions {
+        proxy: Some(Proxy {
+            addr: proxy.to_string(),
+            auth: Some(("steve".to_string(), password.to_string())),
+        }),
+        ..FetchOptions::default()
+    };
+
+    let (response, _) = fetch_status(&server.to_string(), &proxy_with("hunter2"))
+        .await
+        .unwrap();
+    assert_eq!(response.version.name, "Mock 1.19.4");
+
+    let error = fetch_status(&server.to_string(), &proxy_with("wrong"))
+        .await
+        .unwrap_err();
+    assert!(format!("{:?}", error).contains("rejected"));
+}